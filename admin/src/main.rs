@@ -0,0 +1,354 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read as _, Write as _};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, anyhow};
+use clap::{Parser, Subcommand};
+use keystore_rs::{KeyChain, KeyStore};
+use prism_be::config::{AppConfig, parse_config};
+use prism_be::utils::parse_cosmos_adr36_verifying_key;
+use prism_be_client::PrismBeClient;
+use prism_client::SigningKey;
+
+use crate::wallet::{KeyAlgorithm, generate_key, parse_payload, parse_signing_key, sign_payload};
+
+mod wallet;
+
+// Account commands go through the HTTP API, since the off-chain database lives
+// in the server process. The service key is read directly from the keystore.
+#[derive(Parser)]
+#[command(name = "prism-be-admin", about = "Operate a prism-be deployment")]
+struct Cli {
+    #[arg(long, env = "PRISM_BE_URL", default_value = "http://localhost:8080")]
+    #[arg(help = "Base url of the prism-be server")]
+    url: String,
+    #[arg(long, env = "PRISM_BE_ADMIN_TOKEN", hide_env_values = true)]
+    #[arg(help = "Admin token of the server, needed by admin routes")]
+    admin_token: Option<String>,
+    #[arg(long, default_value = "config.toml", help = "Config file of the deployment")]
+    config: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Print the service verifying key from the keystore")]
+    ServiceKey,
+    #[command(about = "Show the service account")]
+    Service,
+    #[command(about = "Register the service with the prover, if it is not registered yet")]
+    RegisterService,
+    #[command(about = "Create an account on behalf of a user from a key file")]
+    CreateAccount {
+        #[arg(long)]
+        id: String,
+        #[arg(long, help = "File holding the user's base64 Cosmos ADR-36 private key")]
+        key_file: PathBuf,
+    },
+    #[command(about = "Show an account")]
+    GetAccount {
+        #[arg(long)]
+        id: String,
+    },
+    #[command(about = "List the ids of all accounts")]
+    ListAccounts,
+    #[command(about = "Export all accounts as JSON")]
+    ExportAccounts {
+        #[arg(long, help = "File to write to, stdout if not given")]
+        output: Option<PathBuf>,
+    },
+    #[command(about = "Add an off-chain key to an account")]
+    AddKey {
+        #[arg(long)]
+        id: String,
+        #[arg(long, help = "Base64 Cosmos ADR-36 verifying key")]
+        verifying_key: String,
+        #[arg(long, help = "Unix timestamp in seconds from which the key is no longer valid")]
+        expires_at: Option<u64>,
+    },
+    #[command(about = "Revoke an off-chain key of an account")]
+    RevokeKey {
+        #[arg(long)]
+        id: String,
+        #[arg(long, help = "Base64 Cosmos ADR-36 verifying key")]
+        verifying_key: String,
+    },
+    #[command(about = "Generate a test wallet key")]
+    Keygen {
+        #[arg(long, value_enum, default_value_t = KeyAlgorithm::CosmosAdr36)]
+        algorithm: KeyAlgorithm,
+        #[arg(long, help = "Key file to write the private key to, stdout if not given")]
+        output: Option<PathBuf>,
+    },
+    #[command(about = "Sign a payload returned by a request endpoint with a key file")]
+    Sign {
+        #[arg(long)]
+        key_file: PathBuf,
+        #[arg(long, value_enum, default_value_t = KeyAlgorithm::CosmosAdr36)]
+        algorithm: KeyAlgorithm,
+        #[arg(long, help = "Response of the request endpoint, JSON byte array or base64")]
+        payload: Option<String>,
+        #[arg(long, conflicts_with = "payload", help = "File holding the payload")]
+        payload_file: Option<PathBuf>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    run(Cli::parse()).await
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    let mut client = PrismBeClient::new(&cli.url)?;
+    if let Some(token) = &cli.admin_token {
+        client = client.with_admin_token(token);
+    }
+
+    match cli.command {
+        Command::ServiceKey => {
+            let config = load_config(&cli.config)?;
+            let service_sk = KeyChain
+                .get_signing_key(&config.service_id)
+                .map_err(|e| anyhow!("Error getting key from store: {}", e))?;
+            let service_sk = SigningKey::Ed25519(Box::new(service_sk));
+            println!("{}", service_sk.verifying_key());
+        }
+        Command::Service => {
+            let config = load_config(&cli.config)?;
+            let view = client.get_account(&config.service_id).await?;
+            println!("{}", serde_json::to_string_pretty(&view)?);
+        }
+        Command::RegisterService => {
+            let service_id = client.register_service().await?;
+            println!("Service {} is registered", service_id);
+        }
+        Command::CreateAccount { id, key_file } => {
+            let signing_key = read_signing_key(&key_file, KeyAlgorithm::CosmosAdr36)?;
+            let id = client.create_account(&id, &signing_key).await?;
+            println!("Created account {}", id);
+        }
+        Command::GetAccount { id } => {
+            let view = client.get_account(&id).await?;
+            println!("{}", serde_json::to_string_pretty(&view)?);
+        }
+        Command::ListAccounts => {
+            for account in client.list_accounts().await? {
+                println!("{}", account.id);
+            }
+        }
+        Command::ExportAccounts { output } => {
+            let accounts = client.list_accounts().await?;
+            let json = serde_json::to_string_pretty(&accounts)?;
+            match output {
+                Some(path) => {
+                    fs::write(&path, json)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    println!("Exported {} accounts to {}", accounts.len(), path.display());
+                }
+                None => println!("{}", json),
+            }
+        }
+        Command::AddKey { id, verifying_key, expires_at } => {
+            let key = parse_cosmos_adr36_verifying_key(verifying_key)?;
+            client.add_key(&id, &key, expires_at).await?;
+            println!("Added key to account {}", id);
+        }
+        Command::RevokeKey { id, verifying_key } => {
+            let key = parse_cosmos_adr36_verifying_key(verifying_key)?;
+            client.revoke_key(&id, &key).await?;
+            println!("Revoked key of account {}", id);
+        }
+        Command::Keygen { algorithm, output } => {
+            warn_unsupported(algorithm);
+            let generated = generate_key(algorithm)?;
+            match output {
+                Some(path) => {
+                    write_private_key(&path, &generated.private_key)?;
+                    println!("{}", generated.verifying_key);
+                }
+                None => println!("{}", serde_json::to_string_pretty(&generated)?),
+            }
+        }
+        Command::Sign { key_file, algorithm, payload, payload_file } => {
+            warn_unsupported(algorithm);
+            let signing_key = read_signing_key(&key_file, algorithm)?;
+            let input = match (payload, payload_file) {
+                (Some(payload), _) => payload,
+                (None, Some(path)) => fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?,
+                (None, None) => {
+                    let mut input = String::new();
+                    io::stdin().read_to_string(&mut input)?;
+                    input
+                }
+            };
+            let signed = sign_payload(&signing_key, &parse_payload(&input)?)?;
+            println!("{}", serde_json::to_string_pretty(&signed)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn load_config(path: &Path) -> anyhow::Result<AppConfig> {
+    parse_config(path).with_context(|| format!("Failed to read config {}", path.display()))
+}
+
+// Read a base64 private key from a file
+fn read_signing_key(path: &Path, algorithm: KeyAlgorithm) -> anyhow::Result<SigningKey> {
+    let encoded = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read key file {}", path.display()))?;
+    parse_signing_key(&encoded, algorithm)
+}
+
+// Write a private key to a new file only its owner can read, refusing to
+// overwrite an existing file
+fn write_private_key(path: &Path, private_key: &str) -> anyhow::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file =
+        options.open(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(private_key.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn warn_unsupported(algorithm: KeyAlgorithm) {
+    if algorithm != KeyAlgorithm::CosmosAdr36 {
+        eprintln!("Warning: the send endpoints currently only accept Cosmos ADR-36 keys");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::Request;
+    use axum::http::StatusCode;
+    use axum::http::header::AUTHORIZATION;
+    use axum::response::IntoResponse;
+    use axum::{Json, Router};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::wallet::generate_key;
+
+    const ADMIN_TOKEN: &str = "secret";
+
+    // Method, path and authorization header of the requests a mock server got
+    type Received = Arc<Mutex<Vec<(String, String, Option<String>)>>>;
+
+    // Serve canned responses to the routes the commands call, requiring the
+    // admin token on admin routes, and record the requests
+    async fn start_mock_server() -> (String, Received) {
+        let received = Received::default();
+        let recorded = received.clone();
+        let router = Router::new().fallback(move |req: Request| {
+            let recorded = recorded.clone();
+            async move {
+                let path = req.uri().path().to_string();
+                let auth = req.headers().get(AUTHORIZATION).and_then(|auth| auth.to_str().ok());
+                let auth = auth.map(str::to_string);
+                let entry = (req.method().to_string(), path.clone(), auth.clone());
+                recorded.lock().unwrap().push(entry);
+
+                let admin = path.starts_with("/v1/admin/") || path == "/v1/account/revoke-key";
+                if admin && auth != Some(format!("Bearer {}", ADMIN_TOKEN)) {
+                    let message = "Error: Missing or invalid admin token";
+                    return (StatusCode::UNAUTHORIZED, message).into_response();
+                }
+                match path.as_str() {
+                    "/v1/account/list-accounts" => Json(json!({ "accounts": [] })).into_response(),
+                    _ => Json(json!({ "id": "alice" })).into_response(),
+                }
+            }
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (url, received)
+    }
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(["prism-be-admin"].iter().chain(args))
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let revoke = ["revoke-key", "--id", "alice", "--verifying-key", "k"];
+        let cli = parse(&[&["--admin-token", "t"][..], &revoke].concat()).unwrap();
+        assert_eq!(cli.admin_token.as_deref(), Some("t"));
+        match cli.command {
+            Command::RevokeKey { id, verifying_key } => {
+                assert_eq!((id.as_str(), verifying_key.as_str()), ("alice", "k"));
+            }
+            _ => panic!("expected the revoke-key command"),
+        }
+
+        let add_key = ["add-key", "--id", "alice", "--verifying-key", "k", "--expires-at"];
+        let cli = parse(&[&add_key[..], &["5"]].concat()).unwrap();
+        assert!(matches!(cli.command, Command::AddKey { expires_at: Some(5), .. }));
+        assert!(parse(&[&add_key[..], &["soon"]].concat()).is_err());
+        assert!(parse(&["revoke-key", "--id", "alice"]).is_err());
+        let sign = ["sign", "--key-file", "key", "--payload", "p", "--payload-file", "f"];
+        assert!(parse(&sign).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_commands_call_the_api() {
+        let (url, received) = start_mock_server().await;
+        let verifying_key = generate_key(KeyAlgorithm::CosmosAdr36).unwrap().verifying_key;
+        let base = ["--url", url.as_str(), "--admin-token", ADMIN_TOKEN];
+
+        let revoke = ["revoke-key", "--id", "alice", "--verifying-key", verifying_key.as_str()];
+        for command in [&["register-service"][..], &revoke, &["list-accounts"]] {
+            run(parse(&[&base[..], command].concat()).unwrap()).await.unwrap();
+        }
+
+        let bearer = Some(format!("Bearer {}", ADMIN_TOKEN));
+        let received = received.lock().unwrap().clone();
+        assert_eq!(
+            received,
+            vec![
+                ("POST".to_string(), "/v1/admin/register-service".to_string(), bearer.clone()),
+                ("POST".to_string(), "/v1/account/revoke-key".to_string(), bearer),
+                ("GET".to_string(), "/v1/account/list-accounts".to_string(), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keygen_writes_a_private_file() {
+        let path = std::env::temp_dir().join(format!("prism-be-keygen-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let keygen = ["keygen", "--output", path.to_str().unwrap()];
+        run(parse(&keygen).unwrap()).await.unwrap();
+        let private_key = fs::read_to_string(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        // An existing key is never overwritten
+        assert!(run(parse(&keygen).unwrap()).await.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), private_key);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_commands_need_the_token() {
+        let (url, received) = start_mock_server().await;
+
+        let cli = parse(&["--url", url.as_str(), "register-service"]).unwrap();
+        let error = run(cli).await.unwrap_err();
+        let status = error.downcast_ref::<prism_be_client::ApiError>().unwrap().status;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(received.lock().unwrap()[0].2, None);
+    }
+}
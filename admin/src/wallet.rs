@@ -0,0 +1,110 @@
+use anyhow::anyhow;
+use clap::ValueEnum;
+use prism_be::api::RequestCreateAccountResponse;
+use prism_client::SigningKey;
+use prism_keys::CryptoAlgorithm;
+use prism_serde::base64::{FromBase64, ToBase64};
+use serde::Serialize;
+use serde_json::Value;
+
+// Algorithms of the test wallet keys the CLI can generate and sign with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeyAlgorithm {
+    CosmosAdr36,
+    Ed25519,
+    Secp256r1,
+}
+
+impl From<KeyAlgorithm> for CryptoAlgorithm {
+    fn from(algorithm: KeyAlgorithm) -> Self {
+        match algorithm {
+            KeyAlgorithm::CosmosAdr36 => CryptoAlgorithm::CosmosAdr36,
+            KeyAlgorithm::Ed25519 => CryptoAlgorithm::Ed25519,
+            KeyAlgorithm::Secp256r1 => CryptoAlgorithm::Secp256r1,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct GeneratedKey {
+    // Base64 private key, the content of a key file
+    pub private_key: String,
+    pub verifying_key: String,
+}
+
+// Verifying key and signature in the format of the send endpoints
+#[derive(Serialize, Debug)]
+pub struct SignedPayload {
+    pub verifying_key: String,
+    pub signature: String,
+}
+
+pub fn generate_key(algorithm: KeyAlgorithm) -> anyhow::Result<GeneratedKey> {
+    let signing_key = SigningKey::new_with_algorithm(algorithm.into())?;
+    Ok(GeneratedKey {
+        private_key: signing_key.to_bytes().to_base64(),
+        verifying_key: signing_key.verifying_key().to_string(),
+    })
+}
+
+// Read a signing key from the base64 content of a key file
+pub fn parse_signing_key(encoded: &str, algorithm: KeyAlgorithm) -> anyhow::Result<SigningKey> {
+    let bytes = Vec::<u8>::from_base64(encoded.trim())?;
+    SigningKey::from_algorithm_and_bytes(algorithm.into(), &bytes)
+}
+
+// Parse the payload to sign, given as the response of a request endpoint
+// (`{"payload": "...", "encoding": "base64"}`), a JSON byte array, or base64
+pub fn parse_payload(input: &str) -> anyhow::Result<Vec<u8>> {
+    let input = input.trim();
+    match serde_json::from_str::<Value>(input) {
+        Ok(response @ Value::Object(_)) => {
+            let response: RequestCreateAccountResponse = serde_json::from_value(response)
+                .map_err(|e| anyhow!("Invalid request response: {}", e))?;
+            response.encoding.decode(&response.payload)
+        }
+        Ok(payload @ Value::Array(_)) => Ok(serde_json::from_value(payload)?),
+        _ => Vec::<u8>::from_base64(input).map_err(|e| anyhow!("Invalid payload: {}", e)),
+    }
+}
+
+pub fn sign_payload(signing_key: &SigningKey, payload: &[u8]) -> anyhow::Result<SignedPayload> {
+    let signature = signing_key.sign(payload)?;
+    Ok(SignedPayload {
+        verifying_key: signing_key.verifying_key().to_string(),
+        signature: signature.to_bytes().to_base64(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use prism_be::utils::parse_signature_bundle;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_payload_formats() {
+        let expected = vec![1, 2, 255];
+        let payload = expected.to_base64();
+        let response = format!(r#"{{"payload": "{}", "encoding": "base64"}}"#, payload);
+        assert_eq!(parse_payload(&response).unwrap(), expected);
+        let response = r#"{"payload": "0102ff", "encoding": "hex"}"#;
+        assert_eq!(parse_payload(response).unwrap(), expected);
+        assert_eq!(parse_payload("[1, 2, 255]").unwrap(), expected);
+        assert_eq!(parse_payload(&expected.to_base64()).unwrap(), expected);
+        assert!(parse_payload(r#"{"id": "alice"}"#).is_err());
+    }
+
+    #[test]
+    fn test_signed_payload_is_accepted_by_send_endpoints() {
+        let generated = generate_key(KeyAlgorithm::CosmosAdr36).unwrap();
+        let signing_key =
+            parse_signing_key(&generated.private_key, KeyAlgorithm::CosmosAdr36).unwrap();
+        assert_eq!(signing_key.verifying_key().to_string(), generated.verifying_key);
+
+        let payload = b"payload".to_vec();
+        let signed = sign_payload(&signing_key, &payload).unwrap();
+        let bundle = parse_signature_bundle(signed.verifying_key, signed.signature).unwrap();
+        bundle.verifying_key.verify_signature(payload, &bundle.signature).unwrap();
+    }
+}
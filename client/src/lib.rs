@@ -0,0 +1,759 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use prism_client::{Signature, SigningKey, VerifyingKey};
+use prism_serde::base64::ToBase64;
+use reqwest::{Method, RequestBuilder, StatusCode, Url};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+// Types of the API, shared with the server
+pub use prism_be::api::*;
+pub use prism_be::batch::{BatchOperation, BatchOperationResult, BatchResult, BatchStatus};
+pub use prism_be::config::LimitsConfig;
+pub use prism_be::data::DataRecord;
+pub use prism_be::history::{AccountEvent, EventKind};
+pub use prism_be::key_expiry::PendingRevocation;
+pub use prism_be::key_metadata::KeyMetadata;
+pub use prism_be::ops::{AccountView, HistoryPage, Source, SourcedData, SourcedValue};
+pub use prism_be::reconciler::{AccountDrift, DriftReport};
+pub use prism_be::recovery::{
+    AccountRecovery, GuardianApproval, RecoveryRequest, RecoverySetup, RecoveryStatus,
+    ServiceKeyGrant,
+};
+pub use prism_be::session::{SessionKey, SessionOperation, SessionPermission};
+
+// Error response returned by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.status)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+// Client of the prism-be HTTP API. Errors returned by the server can be
+// downcast to an `ApiError` to inspect their status. Admin routes need a
+// client built `with_admin_token`.
+#[derive(Debug, Clone)]
+pub struct PrismBeClient {
+    base_url: Url,
+    http: reqwest::Client,
+    admin_token: Option<String>,
+}
+
+impl PrismBeClient {
+    // Client for the server at the given base url, e.g. "http://localhost:8080"
+    pub fn new(base_url: &str) -> anyhow::Result<Self> {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    // Client sending its requests through the given HTTP client
+    pub fn with_http_client(base_url: &str, http: reqwest::Client) -> anyhow::Result<Self> {
+        let base_url = Url::parse(base_url)?;
+        if base_url.cannot_be_a_base() {
+            return Err(anyhow!("Invalid base url {}", base_url));
+        }
+        Ok(Self { base_url, http, admin_token: None })
+    }
+
+    // Client sending the given token with requests to admin routes
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    pub async fn health(&self) -> anyhow::Result<()> {
+        let response = self.request(Method::GET, "v1/health", None)?.send().await?;
+        check_status(response).await.map(|_| ())
+    }
+
+    pub async fn limits(&self) -> anyhow::Result<LimitsConfig> {
+        self.send(self.request(Method::GET, "v1/limits", None)?).await
+    }
+
+    pub async fn get_account(&self, id: &str) -> anyhow::Result<AccountView> {
+        let request = self.request(Method::GET, "v1/account/get", None)?;
+        self.send(request.query(&AccountQuery { id: id.to_string() })).await
+    }
+
+    pub async fn get_keys(&self, id: &str) -> anyhow::Result<Vec<SourcedValue>> {
+        let request = self.request(Method::GET, "v1/account/get-key", None)?;
+        let response: GetKeyResponse =
+            self.send(request.query(&AccountQuery { id: id.to_string() })).await?;
+        Ok(response.key)
+    }
+
+    pub async fn get_data(&self, id: &str) -> anyhow::Result<Vec<SourcedData>> {
+        let request = self.request(Method::GET, "v1/account/get-data", None)?;
+        let response: GetDataResponse =
+            self.send(request.query(&AccountQuery { id: id.to_string() })).await?;
+        Ok(response.data)
+    }
+
+    pub async fn list_accounts(&self) -> anyhow::Result<Vec<AccountView>> {
+        let request = self.request(Method::GET, "v1/account/list-accounts", None)?;
+        let response: ListAccountsResponse = self.send(request).await?;
+        Ok(response.accounts)
+    }
+
+    pub async fn list_keys(&self, id: &str) -> anyhow::Result<Vec<SourcedValue>> {
+        self.send(self.request(Method::GET, "v1/account/list-keys", Some(id))?).await
+    }
+
+    // Ids of the accounts holding the given key
+    pub async fn accounts_by_key(
+        &self,
+        verifying_key: &VerifyingKey,
+    ) -> anyhow::Result<Vec<String>> {
+        let query = AccountsByKeyQuery { verifying_key: verifying_key.to_string() };
+        let request = self.request(Method::GET, "v1/account/by-key", None)?;
+        let response: AccountsByKeyResponse = self.send(request.query(&query)).await?;
+        Ok(response.accounts)
+    }
+
+    pub async fn history(
+        &self,
+        id: &str,
+        cursor: Option<u64>,
+        limit: Option<usize>,
+    ) -> anyhow::Result<HistoryPage> {
+        let query = HistoryQuery { id: id.to_string(), cursor, limit };
+        let request = self.request(Method::GET, "v1/account/history", None)?;
+        self.send(request.query(&query)).await
+    }
+
+    // Add an account to the off-chain database only. Admin route.
+    pub async fn add_account_manual(&self, id: &str) -> anyhow::Result<String> {
+        let body = AddAccountRequest { id: id.to_string() };
+        let request = self.admin_request(Method::POST, "v1/account/add-manual", None)?;
+        let response: AccountResult = self.send(request.json(&body)).await?;
+        Ok(response.id)
+    }
+
+    // Payload the key has to sign to create the account
+    pub async fn request_create_account(
+        &self,
+        id: &str,
+        verifying_key: &VerifyingKey,
+    ) -> anyhow::Result<Vec<u8>> {
+        let verifying_key = verifying_key.to_string();
+        let body = RequestCreateAccountRequest { id: id.to_string(), verifying_key };
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/request-create", &body).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_create_account(
+        &self,
+        id: &str,
+        verifying_key: &VerifyingKey,
+        signature: &Signature,
+    ) -> anyhow::Result<String> {
+        let body = SendCreateAccountRequest {
+            id: id.to_string(),
+            verifying_key: verifying_key.to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        let response: AccountResult = self.post("v1/account/send-create", &body).await?;
+        Ok(response.id)
+    }
+
+    // Create an account owned by the given key, signing the creation payload
+    // locally. The server only accepts Cosmos ADR-36 keys.
+    pub async fn create_account(
+        &self,
+        id: &str,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<String> {
+        let verifying_key = signing_key.verifying_key();
+        let payload = self.request_create_account(id, &verifying_key).await?;
+        let signature = signing_key.sign(&payload)?;
+        self.send_create_account(id, &verifying_key, &signature).await
+    }
+
+    // Add a key to the account, valid until the given unix timestamp if any
+    pub async fn add_key(
+        &self,
+        id: &str,
+        verifying_key: &VerifyingKey,
+        expires_at: Option<u64>,
+    ) -> anyhow::Result<String> {
+        let verifying_key = verifying_key.to_string();
+        let body = AddKeyRequest { id: id.to_string(), verifying_key, expires_at };
+        let response: AccountResult = self.post("v1/account/add-key", &body).await?;
+        Ok(response.id)
+    }
+
+    // Revoke an off-chain key of an account
+    pub async fn revoke_key(
+        &self,
+        id: &str,
+        verifying_key: &VerifyingKey,
+    ) -> anyhow::Result<String> {
+        let verifying_key = verifying_key.to_string();
+        let body = RevokeKeyRequest { id: id.to_string(), verifying_key };
+        let request = self.admin_request(Method::POST, "v1/account/revoke-key", None)?;
+        let response: AccountResult = self.send(request.json(&body)).await?;
+        Ok(response.id)
+    }
+
+    // Payload an on-chain account key has to sign to change the metadata of a
+    // key of the account
+    pub async fn request_key_metadata(
+        &self,
+        request: &RequestKeyMetadataRequest,
+    ) -> anyhow::Result<Vec<u8>> {
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/request-key-metadata", request).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_key_metadata(
+        &self,
+        request: &UpdateKeyMetadataRequest,
+    ) -> anyhow::Result<KeyMetadata> {
+        self.post("v1/account/send-key-metadata", request).await
+    }
+
+    // Set the label or wallet type of a key of the account, signing the change
+    // locally with an on-chain key of the account
+    pub async fn update_key_metadata(
+        &self,
+        request: RequestKeyMetadataRequest,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<KeyMetadata> {
+        let payload = self.request_key_metadata(&request).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = UpdateKeyMetadataRequest {
+            id: request.id,
+            key: request.key,
+            label: request.label,
+            wallet_type: request.wallet_type,
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_key_metadata(&request).await
+    }
+
+    // Expired on-chain keys of the account waiting for a revoke transaction
+    pub async fn pending_revocations(&self, id: &str) -> anyhow::Result<Vec<PendingRevocation>> {
+        let request = self.request(Method::GET, "v1/account/pending-revocations", None)?;
+        let response: PendingRevocationsResponse =
+            self.send(request.query(&AccountQuery { id: id.to_string() })).await?;
+        Ok(response.revocations)
+    }
+
+    // Payload an on-chain account key has to sign to revoke an expired key
+    pub async fn request_revoke_expired_key(
+        &self,
+        id: &str,
+        key: &VerifyingKey,
+    ) -> anyhow::Result<Vec<u8>> {
+        let body = RequestRevokeExpiredKeyRequest { id: id.to_string(), key: key.to_string() };
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/request-revoke-expired", &body).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_revoke_expired_key(
+        &self,
+        request: &SendRevokeExpiredKeyRequest,
+    ) -> anyhow::Result<PendingRevocation> {
+        self.post("v1/account/send-revoke-expired", request).await
+    }
+
+    // Revoke an expired on-chain key, signing the revoke transaction locally
+    // with a key of the account, which may be the expired key itself
+    pub async fn revoke_expired_key(
+        &self,
+        id: &str,
+        key: &VerifyingKey,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<PendingRevocation> {
+        let payload = self.request_revoke_expired_key(id, key).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = SendRevokeExpiredKeyRequest {
+            id: id.to_string(),
+            key: key.to_string(),
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_revoke_expired_key(&request).await
+    }
+
+    pub async fn add_data(&self, request: &AddDataRequest) -> anyhow::Result<String> {
+        let response: AccountResult = self.post("v1/account/add-data", request).await?;
+        Ok(response.id)
+    }
+
+    // Payload an account key has to sign to authorize a data change
+    pub async fn request_data_change(
+        &self,
+        request: &RequestDataChangeRequest,
+    ) -> anyhow::Result<Vec<u8>> {
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/request-change-data", request).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_data_change(
+        &self,
+        request: &SendDataChangeRequest,
+    ) -> anyhow::Result<DataRecord> {
+        let response: DataRecordResponse =
+            self.post("v1/account/send-change-data", request).await?;
+        Ok(response.record)
+    }
+
+    // Replace or delete a data entry, signing the change payload locally with
+    // a key of the account
+    pub async fn change_data(
+        &self,
+        request: RequestDataChangeRequest,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<DataRecord> {
+        let payload = self.request_data_change(&request).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = SendDataChangeRequest {
+            id: request.id,
+            entry_id: request.entry_id,
+            action: request.action,
+            data: request.data,
+            type_tag: request.type_tag,
+            content_type: request.content_type,
+            schema: request.schema,
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_data_change(&request).await
+    }
+
+    pub async fn list_sessions(&self, id: &str) -> anyhow::Result<Vec<SessionKey>> {
+        let request = self.request(Method::GET, "v1/account/sessions", None)?;
+        let response: ListSessionKeysResponse =
+            self.send(request.query(&AccountQuery { id: id.to_string() })).await?;
+        Ok(response.sessions)
+    }
+
+    // Payload an on-chain account key has to sign to authorize a session key
+    pub async fn request_session_key(
+        &self,
+        request: &RequestSessionKeyRequest,
+    ) -> anyhow::Result<Vec<u8>> {
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/session/request-create", request).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_session_key(
+        &self,
+        request: &SendSessionKeyRequest,
+    ) -> anyhow::Result<SessionKey> {
+        self.post("v1/account/session/send-create", request).await
+    }
+
+    // Authorize a session key, signing the grant payload locally with a key of
+    // the account
+    pub async fn create_session_key(
+        &self,
+        request: RequestSessionKeyRequest,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<SessionKey> {
+        let payload = self.request_session_key(&request).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = SendSessionKeyRequest {
+            id: request.id,
+            session_key: request.session_key,
+            permissions: request.permissions,
+            expires_at: request.expires_at,
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_session_key(&request).await
+    }
+
+    pub async fn revoke_session_key(
+        &self,
+        id: &str,
+        session_key: &VerifyingKey,
+    ) -> anyhow::Result<SessionKey> {
+        let session_key = session_key.to_string();
+        let body = RevokeSessionKeyRequest { id: id.to_string(), session_key };
+        self.post("v1/account/session/revoke", &body).await
+    }
+
+    // Payload a session key has to sign to add a data entry
+    pub async fn request_session_data(
+        &self,
+        request: &RequestSessionDataRequest,
+    ) -> anyhow::Result<Vec<u8>> {
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/session/request-add-data", request).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_session_data(
+        &self,
+        request: &AddSessionDataRequest,
+    ) -> anyhow::Result<DataRecord> {
+        let response: DataRecordResponse =
+            self.post("v1/account/session/add-data", request).await?;
+        Ok(response.record)
+    }
+
+    // Add a data entry signed locally with a session key
+    pub async fn add_session_data(
+        &self,
+        request: RequestSessionDataRequest,
+        session_key: &SigningKey,
+    ) -> anyhow::Result<DataRecord> {
+        let payload = self.request_session_data(&request).await?;
+        let signature = session_key.sign(&payload)?;
+        let request = AddSessionDataRequest {
+            id: request.id,
+            nonce: request.nonce,
+            data: request.data,
+            type_tag: request.type_tag,
+            content_type: request.content_type,
+            schema: request.schema,
+            session_key: session_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_session_data(&request).await
+    }
+
+    // Guardians of the account and the recoveries started for it
+    pub async fn recovery(&self, id: &str) -> anyhow::Result<AccountRecovery> {
+        let request = self.request(Method::GET, "v1/account/recovery", None)?;
+        self.send(request.query(&AccountQuery { id: id.to_string() })).await
+    }
+
+    // Payload an on-chain account key has to sign to set the guardians
+    pub async fn request_recovery_setup(
+        &self,
+        request: &RequestRecoverySetupRequest,
+    ) -> anyhow::Result<Vec<u8>> {
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/recovery/request-setup", request).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_recovery_setup(
+        &self,
+        request: &SendRecoverySetupRequest,
+    ) -> anyhow::Result<RecoverySetup> {
+        self.post("v1/account/recovery/send-setup", request).await
+    }
+
+    // Set the guardians of the account, signing the setup payload locally with
+    // a key of the account
+    pub async fn set_guardians(
+        &self,
+        request: RequestRecoverySetupRequest,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<RecoverySetup> {
+        let payload = self.request_recovery_setup(&request).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = SendRecoverySetupRequest {
+            id: request.id,
+            guardians: request.guardians,
+            threshold: request.threshold,
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_recovery_setup(&request).await
+    }
+
+    // Payload a key of a guardian has to sign to start a recovery
+    pub async fn request_recovery_start(
+        &self,
+        id: &str,
+        new_key: &VerifyingKey,
+    ) -> anyhow::Result<Vec<u8>> {
+        let body = RequestStartRecoveryRequest { id: id.to_string(), new_key: new_key.to_string() };
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/recovery/request-start", &body).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_recovery_start(
+        &self,
+        request: &StartRecoveryRequest,
+    ) -> anyhow::Result<RecoveryRequest> {
+        self.post("v1/account/recovery/start", request).await
+    }
+
+    // Start the recovery of an account as one of its guardians, signing the
+    // start payload locally with a key of the guardian account
+    pub async fn start_recovery(
+        &self,
+        id: &str,
+        new_key: &VerifyingKey,
+        guardian: &str,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<RecoveryRequest> {
+        let payload = self.request_recovery_start(id, new_key).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = StartRecoveryRequest {
+            id: id.to_string(),
+            new_key: new_key.to_string(),
+            guardian: guardian.to_string(),
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_recovery_start(&request).await
+    }
+
+    // Payload a key of a guardian has to sign to approve a recovery
+    pub async fn request_recovery_approval(
+        &self,
+        id: &str,
+        recovery_id: &str,
+    ) -> anyhow::Result<Vec<u8>> {
+        let body = recovery_action(id, recovery_id);
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/recovery/request-approve", &body).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_recovery_approval(
+        &self,
+        request: &ApproveRecoveryRequest,
+    ) -> anyhow::Result<RecoveryRequest> {
+        self.post("v1/account/recovery/approve", request).await
+    }
+
+    // Approve a recovery as one of the guardians, signing the approval payload
+    // locally with a key of the guardian account
+    pub async fn approve_recovery(
+        &self,
+        id: &str,
+        recovery_id: &str,
+        guardian: &str,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<RecoveryRequest> {
+        let payload = self.request_recovery_approval(id, recovery_id).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = ApproveRecoveryRequest {
+            id: id.to_string(),
+            recovery_id: recovery_id.to_string(),
+            guardian: guardian.to_string(),
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_recovery_approval(&request).await
+    }
+
+    // Payload a key of the account has to sign to cancel a recovery
+    pub async fn request_recovery_cancel(
+        &self,
+        id: &str,
+        recovery_id: &str,
+    ) -> anyhow::Result<Vec<u8>> {
+        let body = recovery_action(id, recovery_id);
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/recovery/request-cancel", &body).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_recovery_cancel(
+        &self,
+        request: &CancelRecoveryRequest,
+    ) -> anyhow::Result<RecoveryRequest> {
+        self.post("v1/account/recovery/cancel", request).await
+    }
+
+    // Cancel a recovery, signing the cancel payload locally with a key of the
+    // account
+    pub async fn cancel_recovery(
+        &self,
+        id: &str,
+        recovery_id: &str,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<RecoveryRequest> {
+        let payload = self.request_recovery_cancel(id, recovery_id).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = CancelRecoveryRequest {
+            id: id.to_string(),
+            recovery_id: recovery_id.to_string(),
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_recovery_cancel(&request).await
+    }
+
+    // Add the key of an approved recovery on-chain once its time lock expired
+    pub async fn execute_recovery(
+        &self,
+        id: &str,
+        recovery_id: &str,
+    ) -> anyhow::Result<RecoveryRequest> {
+        self.post("v1/account/recovery/execute", &recovery_action(id, recovery_id)).await
+    }
+
+    // Payload an on-chain account key has to sign to add the service key to
+    // the account, which recoveries need
+    pub async fn request_recovery_enable(&self, id: &str) -> anyhow::Result<Vec<u8>> {
+        let body = AccountQuery { id: id.to_string() };
+        let response: RequestCreateAccountResponse =
+            self.post("v1/account/recovery/request-enable", &body).await?;
+        response.encoding.decode(&response.payload)
+    }
+
+    pub async fn send_recovery_enable(
+        &self,
+        request: &SendEnableRecoveryRequest,
+    ) -> anyhow::Result<ServiceKeyGrant> {
+        self.post("v1/account/recovery/send-enable", request).await
+    }
+
+    // Enable recoveries of an account, signing the transaction adding the
+    // service key locally with an on-chain key of the account
+    pub async fn enable_recovery(
+        &self,
+        id: &str,
+        signing_key: &SigningKey,
+    ) -> anyhow::Result<ServiceKeyGrant> {
+        let payload = self.request_recovery_enable(id).await?;
+        let signature = signing_key.sign(&payload)?;
+        let request = SendEnableRecoveryRequest {
+            id: id.to_string(),
+            verifying_key: signing_key.verifying_key().to_string(),
+            signature: signature.to_bytes().to_base64(),
+        };
+        self.send_recovery_enable(&request).await
+    }
+
+    pub async fn batch(
+        &self,
+        operations: Vec<BatchOperation>,
+        atomic: bool,
+    ) -> anyhow::Result<BatchResult> {
+        self.post("v1/batch", &BatchRequest { operations, atomic }).await
+    }
+
+    // Register a new schema, or replace one by giving the version following its
+    // current one. Returns the version of the stored schema.
+    pub async fn register_schema(
+        &self,
+        id: &str,
+        version: Option<u64>,
+        schema: Value,
+    ) -> anyhow::Result<u64> {
+        let body = RegisterSchemaRequest { id: id.to_string(), version, schema };
+        let request = self.admin_request(Method::POST, "v1/schemas", None)?;
+        let response: SchemaResponse = self.send(request.json(&body)).await?;
+        Ok(response.version)
+    }
+
+    pub async fn list_schemas(&self) -> anyhow::Result<Vec<SchemaResponse>> {
+        let response: ListSchemasResponse =
+            self.send(self.request(Method::GET, "v1/schemas", None)?).await?;
+        Ok(response.schemas)
+    }
+
+    pub async fn get_schema(&self, id: &str) -> anyhow::Result<Value> {
+        let response: SchemaResponse =
+            self.send(self.request(Method::GET, "v1/schemas", Some(id))?).await?;
+        Ok(response.schema)
+    }
+
+    pub async fn reserved_ids(&self) -> anyhow::Result<Vec<String>> {
+        let request = self.admin_request(Method::GET, "v1/admin/reserved-ids", None)?;
+        let response: ReservedIdsResponse = self.send(request).await?;
+        Ok(response.reserved)
+    }
+
+    pub async fn reserve_ids(&self, ids: Vec<String>) -> anyhow::Result<Vec<String>> {
+        let request = self.admin_request(Method::POST, "v1/admin/reserved-ids", None)?;
+        let response: ReservedIdsResponse =
+            self.send(request.json(&ReserveIdsRequest { ids })).await?;
+        Ok(response.reserved)
+    }
+
+    pub async fn release_reserved_id(&self, id: &str) -> anyhow::Result<Vec<String>> {
+        let request = self.admin_request(Method::DELETE, "v1/admin/reserved-ids", Some(id))?;
+        let response: ReservedIdsResponse = self.send(request).await?;
+        Ok(response.reserved)
+    }
+
+    // Register the service account with the prover, returning the service id
+    pub async fn register_service(&self) -> anyhow::Result<String> {
+        let request = self.admin_request(Method::POST, "v1/admin/register-service", None)?;
+        let response: AccountResult = self.send(request).await?;
+        Ok(response.id)
+    }
+
+    pub async fn drift_report(&self) -> anyhow::Result<DriftReport> {
+        self.send(self.admin_request(Method::GET, "v1/admin/drift", None)?).await
+    }
+
+    // Request to the given route, with an optional path parameter appended
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        param: Option<&str>,
+    ) -> anyhow::Result<RequestBuilder> {
+        let mut url = self.base_url.clone();
+        {
+            let mut segments =
+                url.path_segments_mut().map_err(|_| anyhow!("Invalid base url {}", self.base_url))?;
+            segments.pop_if_empty().extend(path.split('/'));
+            if let Some(param) = param {
+                segments.push(param);
+            }
+        }
+        Ok(self.http.request(method, url))
+    }
+
+    // Request to an admin route, carrying the admin token if there is one
+    fn admin_request(
+        &self,
+        method: Method,
+        path: &str,
+        param: Option<&str>,
+    ) -> anyhow::Result<RequestBuilder> {
+        let request = self.request(method, path, param)?;
+        Ok(match &self.admin_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        })
+    }
+
+    async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> anyhow::Result<T> {
+        self.send(self.request(Method::POST, path, None)?.json(body)).await
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> anyhow::Result<T> {
+        let response = check_status(request.send().await?).await?;
+        Ok(response.json().await?)
+    }
+}
+
+fn recovery_action(id: &str, recovery_id: &str) -> RecoveryActionRequest {
+    RecoveryActionRequest { id: id.to_string(), recovery_id: recovery_id.to_string() }
+}
+
+// Turn error responses into an `ApiError`
+async fn check_status(response: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let message = body.strip_prefix("Error: ").unwrap_or(&body).to_string();
+    Err(ApiError { status, message }.into())
+}
@@ -0,0 +1,219 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use prism_be::config::AppConfig;
+use prism_be::key_expiry::expire_keys;
+use prism_be_client::{
+    ApiError, PrismBeClient, RecoveryStatus, RequestKeyMetadataRequest,
+    RequestRecoverySetupRequest, RequestSessionDataRequest, RequestSessionKeyRequest,
+    SessionOperation, SessionPermission, Source,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::common::{ADMIN_TOKEN, TestServer, user_key};
+
+// Same fixture as the server's integration tests
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+// Start a server with an in-memory prover on a random port and return a client
+// connected to it, holding the admin token
+async fn start_server() -> PrismBeClient {
+    client(&TestServer::start().await)
+}
+
+fn client(server: &TestServer) -> PrismBeClient {
+    PrismBeClient::new(&server.url("")).unwrap().with_admin_token(ADMIN_TOKEN)
+}
+
+#[tokio::test]
+async fn test_create_account() {
+    let client = start_server().await;
+    let key = user_key();
+
+    let id = client.create_account("alice", &key).await.unwrap();
+    assert_eq!(id, "alice");
+
+    let keys = client.get_keys("alice").await.unwrap();
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].value, key.verifying_key().to_string());
+    assert_ne!(keys[0].source, Source::OffChainOnly);
+
+    let accounts = client.accounts_by_key(&key.verifying_key()).await.unwrap();
+    assert_eq!(accounts, vec!["alice".to_string()]);
+
+    let history = client.history("alice", None, None).await.unwrap();
+    assert_eq!(history.events.len(), 1);
+
+    // Keys headed on-chain cannot be revoked by the service
+    let error = client.revoke_key("alice", &key.verifying_key()).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_errors_carry_status() {
+    let client = start_server().await;
+
+    let error = client.get_schema("missing").await.unwrap_err();
+    let api_error = error.downcast_ref::<ApiError>().unwrap();
+    assert_eq!(api_error.status, StatusCode::NOT_FOUND);
+
+    // The service id is reserved
+    let error = client.add_account_manual("prism-be-id").await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_schemas_and_reserved_ids() {
+    let client = start_server().await;
+
+    let schema = json!({ "type": "object", "required": ["name"] });
+    assert_eq!(client.register_schema("profile", None, schema.clone()).await.unwrap(), 1);
+    assert_eq!(client.get_schema("profile").await.unwrap(), schema);
+    let error = client.register_schema("profile", None, schema.clone()).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, StatusCode::CONFLICT);
+    assert_eq!(client.register_schema("profile", Some(2), schema.clone()).await.unwrap(), 2);
+    assert_eq!(client.list_schemas().await.unwrap().len(), 1);
+
+    let reserved = client.reserve_ids(vec!["support".to_string()]).await.unwrap();
+    assert!(reserved.contains(&"support".to_string()));
+    let reserved = client.release_reserved_id("support").await.unwrap();
+    assert!(!reserved.contains(&"support".to_string()));
+    let error = client.clone().with_admin_token("wrong").reserved_ids().await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, StatusCode::UNAUTHORIZED);
+
+    let limits = client.limits().await.unwrap();
+    assert!(limits.max_body_bytes > 0);
+}
+
+#[tokio::test]
+async fn test_key_metadata() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    server.create_included_account("alice", &key).await;
+    let client = client(&server);
+
+    let request = RequestKeyMetadataRequest {
+        id: "alice".to_string(),
+        key: key.verifying_key().to_string(),
+        label: Some("Phone".to_string()),
+        wallet_type: None,
+    };
+    let metadata = client.update_key_metadata(request.clone(), &key).await.unwrap();
+    assert_eq!(metadata.label.as_deref(), Some("Phone"));
+    // Only on-chain keys of the account can sign the change
+    let error = client.update_key_metadata(request, &user_key()).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, StatusCode::FORBIDDEN);
+    let keys = client.get_keys("alice").await.unwrap();
+    assert_eq!(keys[0].metadata.label.as_deref(), Some("Phone"));
+}
+
+#[tokio::test]
+async fn test_session_keys() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    server.create_included_account("alice", &key).await;
+    let client = client(&server);
+
+    let session = user_key();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let permission =
+        SessionPermission { operation: SessionOperation::AddData, data_types: Vec::new() };
+    let grant = RequestSessionKeyRequest {
+        id: "alice".to_string(),
+        session_key: session.verifying_key().to_string(),
+        permissions: vec![permission],
+        expires_at: now + 3600,
+    };
+    let created = client.create_session_key(grant, &key).await.unwrap();
+    assert_eq!(created.authorized_by, key.verifying_key().to_string());
+
+    let request = RequestSessionDataRequest {
+        id: "alice".to_string(),
+        nonce: 1,
+        data: "hello".to_string(),
+        type_tag: None,
+        content_type: None,
+        schema: None,
+    };
+    let record = client.add_session_data(request.clone(), &session).await.unwrap();
+    assert_eq!(record.value, "hello");
+    // Nonces cannot be reused
+    let error = client.add_session_data(request.clone(), &session).await.unwrap_err();
+    assert!(error.downcast_ref::<ApiError>().is_some());
+
+    let revoked = client.revoke_session_key("alice", &session.verifying_key()).await.unwrap();
+    assert!(revoked.revoked_at.is_some());
+    let sessions = client.list_sessions("alice").await.unwrap();
+    assert_eq!(sessions, vec![revoked]);
+    let request = RequestSessionDataRequest { nonce: 2, ..request };
+    let error = client.add_session_data(request, &session).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_recovery() {
+    let mut config = AppConfig::default();
+    config.rate_limit.enabled = false;
+    config.reconciler.enabled = false;
+    config.recovery.timelock_secs = 0;
+    let server = TestServer::start_with_config(config).await;
+    let (alice, bob) = (user_key(), user_key());
+    server.create_included_account("alice", &alice).await;
+    server.create_included_account("bob", &bob).await;
+    let client = client(&server);
+
+    // Recoveries add their key with the service key the account added
+    let grant = client.enable_recovery("alice", &alice).await.unwrap();
+    assert_eq!(grant.service_key, server.state.service_sk.verifying_key().to_string());
+    server.wait_for_key("alice", &grant.service_key).await;
+    let setup = RequestRecoverySetupRequest {
+        id: "alice".to_string(),
+        guardians: vec!["bob".to_string()],
+        threshold: 1,
+    };
+    assert_eq!(client.set_guardians(setup, &alice).await.unwrap().revision, 1);
+
+    // A single guardian approves the recovery by starting it
+    let new_key = user_key();
+    let recovery =
+        client.start_recovery("alice", &new_key.verifying_key(), "bob", &bob).await.unwrap();
+    let error = client.approve_recovery("alice", &recovery.id, "bob", &bob).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, StatusCode::CONFLICT);
+    let executed = client.execute_recovery("alice", &recovery.id).await.unwrap();
+    assert_eq!(executed.status, RecoveryStatus::Executed);
+    assert!(executed.tx_id.is_some());
+    server.wait_for_key("alice", &new_key.verifying_key().to_string()).await;
+
+    // The recovered key signs for the account
+    let other_key = user_key().verifying_key();
+    let other = client.start_recovery("alice", &other_key, "bob", &bob).await.unwrap();
+    let cancelled = client.cancel_recovery("alice", &other.id, &new_key).await.unwrap();
+    assert_eq!(cancelled.status, RecoveryStatus::Cancelled);
+    let recovery = client.recovery("alice").await.unwrap();
+    assert_eq!(recovery.recoveries, vec![executed, cancelled]);
+}
+
+#[tokio::test]
+async fn test_expired_key_revocation() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key();
+    server.create_included_account("alice", &key).await;
+    let client = client(&server);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let key_value = verifying_key.to_string();
+    server.state.db.update_key_metadata("alice".to_string(), key_value.clone(), |metadata| {
+        metadata.expires_at = Some(now);
+    });
+    expire_keys(server.state.clone()).await;
+    // Expired keys no longer lead to their accounts
+    assert!(client.accounts_by_key(&verifying_key).await.unwrap().is_empty());
+
+    let pending = client.pending_revocations("alice").await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].key, key_value);
+    let revocation = client.revoke_expired_key("alice", &verifying_key, &key).await.unwrap();
+    assert!(revocation.tx_id.is_some());
+}
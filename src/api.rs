@@ -0,0 +1,379 @@
+use serde::{Deserialize, Serialize};
+
+use crate::batch::BatchOperation;
+use crate::data::DataRecord;
+use crate::encoding::BinaryEncoding;
+use crate::key_expiry::PendingRevocation;
+use crate::ops::{AccountView, SourcedData, SourcedValue};
+use crate::session::{SessionKey, SessionPermission};
+
+// Request and response bodies of the HTTP API, shared with the client crate
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SendCreateAccountRequest {
+    pub id: String,
+    // The verifying key is in base64 format
+    pub verifying_key: String,
+    // The signature is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestCreateAccountRequest {
+    pub id: String,
+    pub verifying_key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AddKeyRequest {
+    pub id: String,
+    pub verifying_key: String,
+    // Unix timestamp in seconds from which the key is no longer valid
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RevokeKeyRequest {
+    pub id: String,
+    pub verifying_key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestRevokeExpiredKeyRequest {
+    pub id: String,
+    // The expired key is in base64 format
+    pub key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SendRevokeExpiredKeyRequest {
+    pub id: String,
+    pub key: String,
+    // Account key that signed the revoke transaction, in base64 format
+    pub verifying_key: String,
+    // The signature over the transaction payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PendingRevocationsResponse {
+    pub revocations: Vec<PendingRevocation>,
+    pub encoding: BinaryEncoding,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestKeyMetadataRequest {
+    pub id: String,
+    // The described key is in base64 format
+    pub key: String,
+    // Fields left out are kept, empty strings clear them
+    pub label: Option<String>,
+    pub wallet_type: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UpdateKeyMetadataRequest {
+    pub id: String,
+    pub key: String,
+    pub label: Option<String>,
+    pub wallet_type: Option<String>,
+    // On-chain account key that signed the change, in base64 format
+    pub verifying_key: String,
+    // The signature over the key metadata payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AddDataRequest {
+    pub id: String,
+    pub data: String,
+    // Application defined type of the entry, defaults to "text"
+    #[serde(rename = "type")]
+    pub type_tag: Option<String>,
+    // Defaults to "application/json" when a schema is given, "text/plain" otherwise
+    pub content_type: Option<String>,
+    // Id of a registered schema to validate the data against
+    pub schema: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataAction {
+    Replace,
+    Delete,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestDataChangeRequest {
+    pub id: String,
+    pub entry_id: String,
+    pub action: DataAction,
+    // New value and metadata of the entry, required to replace it
+    pub data: Option<String>,
+    #[serde(rename = "type")]
+    pub type_tag: Option<String>,
+    pub content_type: Option<String>,
+    pub schema: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SendDataChangeRequest {
+    pub id: String,
+    pub entry_id: String,
+    pub action: DataAction,
+    pub data: Option<String>,
+    #[serde(rename = "type")]
+    pub type_tag: Option<String>,
+    pub content_type: Option<String>,
+    pub schema: Option<String>,
+    // The verifying key is in base64 format
+    pub verifying_key: String,
+    // The signature over the change payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestSessionKeyRequest {
+    pub id: String,
+    // The session key is in base64 format
+    pub session_key: String,
+    pub permissions: Vec<SessionPermission>,
+    // Unix timestamp in seconds at which the session key stops being accepted
+    pub expires_at: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SendSessionKeyRequest {
+    pub id: String,
+    pub session_key: String,
+    pub permissions: Vec<SessionPermission>,
+    pub expires_at: u64,
+    // Account key that signed the authorization, in base64 format
+    pub verifying_key: String,
+    // The signature over the session key payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RevokeSessionKeyRequest {
+    pub id: String,
+    pub session_key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ListSessionKeysResponse {
+    pub sessions: Vec<SessionKey>,
+    pub encoding: BinaryEncoding,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestSessionDataRequest {
+    pub id: String,
+    // Has to be higher than the last nonce used by the session key
+    pub nonce: u64,
+    pub data: String,
+    #[serde(rename = "type")]
+    pub type_tag: Option<String>,
+    pub content_type: Option<String>,
+    pub schema: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AddSessionDataRequest {
+    pub id: String,
+    pub nonce: u64,
+    pub data: String,
+    #[serde(rename = "type")]
+    pub type_tag: Option<String>,
+    pub content_type: Option<String>,
+    pub schema: Option<String>,
+    // The session key is in base64 format
+    pub session_key: String,
+    // The signature over the session data payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestRecoverySetupRequest {
+    pub id: String,
+    // Ids of the guardian accounts
+    pub guardians: Vec<String>,
+    // Number of guardians that have to approve a recovery
+    pub threshold: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SendRecoverySetupRequest {
+    pub id: String,
+    pub guardians: Vec<String>,
+    pub threshold: usize,
+    // The verifying key is in base64 format
+    pub verifying_key: String,
+    // The signature over the setup payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestStartRecoveryRequest {
+    pub id: String,
+    // Key to add to the account, in base64 format
+    pub new_key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct StartRecoveryRequest {
+    pub id: String,
+    pub new_key: String,
+    // Id of the guardian account starting the recovery
+    pub guardian: String,
+    // Key of the guardian account, in base64 format
+    pub verifying_key: String,
+    // The signature over the start payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RecoveryActionRequest {
+    pub id: String,
+    pub recovery_id: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ApproveRecoveryRequest {
+    pub id: String,
+    pub recovery_id: String,
+    // Id of the approving guardian account
+    pub guardian: String,
+    // Key of the guardian account, in base64 format
+    pub verifying_key: String,
+    // The signature over the approval payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CancelRecoveryRequest {
+    pub id: String,
+    pub recovery_id: String,
+    // Key of the account, in base64 format
+    pub verifying_key: String,
+    // The signature over the cancel payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SendEnableRecoveryRequest {
+    pub id: String,
+    // On-chain key of the account that signed the transaction adding the
+    // service key, in base64 format
+    pub verifying_key: String,
+    // The signature over the transaction payload is in base64 format
+    pub signature: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AccountResult {
+    pub id: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ListAccountsResponse {
+    pub accounts: Vec<AccountView>,
+}
+
+// Bytes to sign, returned by the request endpoints
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RequestCreateAccountResponse {
+    pub payload: String,
+    pub encoding: BinaryEncoding,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetDataResponse {
+    pub data: Vec<SourcedData>,
+    pub encoding: BinaryEncoding,
+}
+
+// Data entry returned by the endpoints writing one, with the encoding of its
+// value if it is raw on-chain data
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DataRecordResponse {
+    #[serde(flatten)]
+    pub record: DataRecord,
+    pub encoding: BinaryEncoding,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AddAccountRequest {
+    pub id: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetKeyResponse {
+    pub key: Vec<SourcedValue>,
+    pub encoding: BinaryEncoding,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AccountQuery {
+    pub id: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+    // Apply the operations only if all of them are valid
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HistoryQuery {
+    pub id: String,
+    // Sequence number of the first event to return
+    pub cursor: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AccountsByKeyQuery {
+    pub verifying_key: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AccountsByKeyResponse {
+    pub verifying_key: String,
+    pub accounts: Vec<String>,
+    pub encoding: BinaryEncoding,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ReserveIdsRequest {
+    pub ids: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ReservedIdsResponse {
+    pub reserved: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RegisterSchemaRequest {
+    pub id: String,
+    // Version replacing the current one, which has to be the current version
+    // plus one. Omitted when registering a new schema.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    pub schema: serde_json::Value,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SchemaResponse {
+    pub id: String,
+    pub version: u64,
+    pub schema: serde_json::Value,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ListSchemasResponse {
+    pub schemas: Vec<SchemaResponse>,
+}
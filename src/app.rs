@@ -1,24 +1,51 @@
+use std::fmt;
 use std::sync::Arc;
 
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use prism_client::SigningKey;
+use prism_da::DataAvailabilityLayer;
 use prism_prover::Prover;
 
+use crate::config::AppConfig;
 use crate::db::Database;
+use crate::idempotency::IdempotencyStore;
+use crate::rate_limit::RateLimiter;
 // Application state
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<Database>,
     pub prover: Arc<Prover>,
+    pub da: Arc<dyn DataAvailabilityLayer>,
     pub service_id: String,
     pub service_sk: SigningKey,
+    pub config: AppConfig,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub idempotency: Arc<IdempotencyStore>,
 }
 
 impl AppState {
-    pub fn new(prover: Arc<Prover>, service_id: String, service_sk: SigningKey) -> Self {
+    pub fn new(
+        prover: Arc<Prover>,
+        da: Arc<dyn DataAvailabilityLayer>,
+        config: &AppConfig,
+        service_sk: SigningKey,
+    ) -> Self {
         let db = Arc::new(Database::new());
-        Self { prover, service_id, service_sk, db }
+        db.insert_reserved_ids(config.account_id.reserved.clone());
+        let service_id = config.service_id.clone();
+        let rate_limiter = Arc::new(RateLimiter::new());
+        let idempotency = Arc::new(IdempotencyStore::new(config.idempotency.max_entries));
+        Self {
+            prover,
+            da,
+            service_id,
+            service_sk,
+            db,
+            config: config.clone(),
+            rate_limiter,
+            idempotency,
+        }
     }
 }
 
@@ -26,10 +53,46 @@ impl AppState {
 #[derive(Debug)]
 pub struct AppError(pub anyhow::Error);
 
+// Error carrying the HTTP status it should be reported with. It can be
+// attached anywhere in an anyhow error chain, either as the source error or as
+// context.
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl StatusError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
 // Convert errors to responses
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", self.0)).into_response()
+        let status = self
+            .0
+            .downcast_ref::<StatusError>()
+            .map(|e| e.status)
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, format!("Error: {:#}", self.0)).into_response()
     }
 }
 
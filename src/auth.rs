@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use axum::extract::FromRequestParts;
+use axum::http::StatusCode;
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+
+use crate::app::{AppError, AppState, StatusError};
+
+// Proof that a request carries the admin token of the config as a bearer
+// token. Handlers of admin routes take it as their first argument.
+pub struct AdminAuth;
+
+impl FromRequestParts<Arc<AppState>> for AdminAuth {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        // Without a configured token the admin routes are closed
+        let Some(token) = state.config.admin.token.as_deref().filter(|token| !token.is_empty())
+        else {
+            let message = "Admin API is disabled, set admin.token to enable it";
+            return Err(AppError(StatusError::new(StatusCode::FORBIDDEN, message).into()));
+        };
+        let provided = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match provided {
+            Some(provided) if constant_time_eq(provided.as_bytes(), token.as_bytes()) => {
+                Ok(AdminAuth)
+            }
+            _ => {
+                let message = "Missing or invalid admin token";
+                Err(AppError(StatusError::new(StatusCode::UNAUTHORIZED, message).into()))
+            }
+        }
+    }
+}
+
+// Compare two byte strings in time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret-longer"));
+    }
+}
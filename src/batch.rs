@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use prism_client::{Account, PrismApi as _, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{AppState, StatusError};
+use crate::data::{DataRecord, NewData};
+use crate::ops::{
+    CreateAccountTx, build_create_account, check_data_quota, fetch_prover_account,
+    lookup_account_id, store_data, store_key, submit_create_account, validate_account_id,
+    validate_key_expiry, validate_new_data,
+};
+use crate::rate_limit::{check_route, retry_after_secs, route_buckets};
+use crate::utils::{parse_cosmos_adr36_verifying_key, parse_signature_bundle};
+
+// Operation of a batch request, with the same fields as the matching endpoint
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    CreateAccount {
+        id: String,
+        verifying_key: String,
+        signature: String,
+    },
+    AddKey {
+        id: String,
+        verifying_key: String,
+        expires_at: Option<u64>,
+    },
+    AddData {
+        id: String,
+        data: String,
+        #[serde(rename = "type")]
+        type_tag: Option<String>,
+        content_type: Option<String>,
+        schema: Option<String>,
+    },
+}
+
+impl BatchOperation {
+    // Route of the endpoint matching the operation, whose rate limits apply
+    fn route(&self) -> &'static str {
+        match self {
+            BatchOperation::CreateAccount { .. } => "/v1/account/send-create",
+            BatchOperation::AddKey { .. } => "/v1/account/add-key",
+            BatchOperation::AddData { .. } => "/v1/account/add-data",
+        }
+    }
+
+    fn account_id(&self) -> &str {
+        match self {
+            BatchOperation::CreateAccount { id, .. }
+            | BatchOperation::AddKey { id, .. }
+            | BatchOperation::AddData { id, .. } => id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Ok,
+    Failed,
+    // Not applied because another operation of an all-or-nothing batch failed
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchOperationResult {
+    pub index: usize,
+    pub status: BatchStatus,
+    // Id of the account the operation applied to
+    pub id: Option<String>,
+    // Id of the data entry added by the operation
+    pub data_id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchResult {
+    // Whether every operation was applied
+    pub applied: bool,
+    pub results: Vec<BatchOperationResult>,
+}
+
+// Operation validated against the simulated state of the batch, ready to be
+// applied
+enum PreparedOperation {
+    CreateAccount(CreateAccountTx),
+    // Account creation for an account that exists already
+    ExistingAccount(String),
+    AddKey { user_id: String, key: VerifyingKey, expires_at: Option<u64> },
+    AddData { user_id: String, data: NewData },
+}
+
+// Accounts as they would be after the operations of the batch validated so far
+#[derive(Default)]
+struct Simulation {
+    accounts: HashMap<String, Account>,
+    // Data entries added to each account by the batch
+    added_data: HashMap<String, Vec<DataRecord>>,
+}
+
+// Execute an ordered list of operations. Every operation is validated against
+// the accounts as left by the previous ones, with account creations processed
+// on a simulated account before anything is queued to the prover.
+// Each operation is charged against the rate limits of its matching endpoint,
+// for the client IP and the account it targets, and fails when they are
+// exhausted.
+// With `atomic`, nothing is applied unless every operation validates, and the
+// operations are only charged once the batch is applied; transactions already
+// queued to the prover cannot be rolled back if applying a later operation
+// fails.
+pub async fn execute_batch(
+    app: Arc<AppState>,
+    operations: Vec<BatchOperation>,
+    atomic: bool,
+    client_ip: Option<IpAddr>,
+) -> anyhow::Result<BatchResult> {
+    let max_operations = app.config.limits.max_batch_operations;
+    if operations.len() > max_operations {
+        return Err(StatusError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Batch exceeds {} operations", max_operations),
+        )
+        .into());
+    }
+
+    let mut simulation = Simulation::default();
+    let mut results = Vec::with_capacity(operations.len());
+
+    if !atomic {
+        for (index, operation) in operations.into_iter().enumerate() {
+            let prepared = match charge(&app, &operation, client_ip) {
+                Ok(()) => prepare(app.clone(), &mut simulation, operation).await,
+                Err(e) => Err(e),
+            };
+            let result = match prepared {
+                Ok(prepared) => apply(app.clone(), index, prepared).await,
+                Err(e) => failed(index, e),
+            };
+            results.push(result);
+        }
+        let applied = results.iter().all(|result| result.status == BatchStatus::Ok);
+        return Ok(BatchResult { applied, results });
+    }
+
+    // The limits are checked before validating, and charged when applying
+    let charges: Vec<_> = operations
+        .iter()
+        .map(|operation| {
+            route_buckets(&app, operation.route(), client_ip, Some(operation.account_id()))
+        })
+        .collect();
+    let routes: Vec<&str> = operations.iter().map(BatchOperation::route).collect();
+    if let Err((index, wait)) = app.rate_limiter.check_all(&charges, false, Instant::now()) {
+        return Ok(rate_limited(routes.len(), index, routes[index], wait));
+    }
+
+    let mut prepared = Vec::with_capacity(operations.len());
+    for operation in operations {
+        prepared.push(prepare(app.clone(), &mut simulation, operation).await);
+    }
+    if prepared.iter().any(Result::is_err) {
+        let results = prepared
+            .into_iter()
+            .enumerate()
+            .map(|(index, prepared)| match prepared {
+                Ok(_) => skipped(index),
+                Err(e) => failed(index, e),
+            })
+            .collect();
+        return Ok(BatchResult { applied: false, results });
+    }
+    // Concurrent requests may have drawn from the buckets while validating
+    if let Err((index, wait)) = app.rate_limiter.check_all(&charges, true, Instant::now()) {
+        return Ok(rate_limited(routes.len(), index, routes[index], wait));
+    }
+    for (index, prepared) in prepared.into_iter().flatten().enumerate() {
+        results.push(apply(app.clone(), index, prepared).await);
+    }
+    let applied = results.iter().all(|result| result.status == BatchStatus::Ok);
+    Ok(BatchResult { applied, results })
+}
+
+// Take a token from the buckets of the endpoint matching an operation
+fn charge(
+    app: &AppState,
+    operation: &BatchOperation,
+    client_ip: Option<IpAddr>,
+) -> anyhow::Result<()> {
+    let route = operation.route();
+    check_route(app, route, client_ip, Some(operation.account_id()))
+        .map_err(|wait| rate_limit_error(route, wait).into())
+}
+
+fn rate_limit_error(route: &str, wait: Duration) -> StatusError {
+    let message = format!("Rate limit of {} exceeded, retry in {}s", route, retry_after_secs(wait));
+    StatusError::new(StatusCode::TOO_MANY_REQUESTS, message)
+}
+
+async fn prepare(
+    app: Arc<AppState>,
+    simulation: &mut Simulation,
+    operation: BatchOperation,
+) -> anyhow::Result<PreparedOperation> {
+    match operation {
+        BatchOperation::CreateAccount { id, verifying_key, signature } => {
+            let user_id = validate_account_id(app.clone(), &id)?;
+            if simulation.accounts.contains_key(&user_id) {
+                return Ok(PreparedOperation::ExistingAccount(user_id));
+            }
+            if let Some(account) = fetch_prover_account(&app, &user_id).await?.account {
+                simulation.accounts.insert(user_id.clone(), account);
+                return Ok(PreparedOperation::ExistingAccount(user_id));
+            }
+            let signature_bundle = parse_signature_bundle(verifying_key, signature)
+                .map_err(|e| e.context(StatusError::bad_request("Invalid signature bundle")))?;
+            let create_tx = build_create_account(app.clone(), user_id.clone(), signature_bundle)?;
+            simulation.accounts.insert(user_id, create_tx.account.clone());
+            Ok(PreparedOperation::CreateAccount(create_tx))
+        }
+        BatchOperation::AddKey { id, verifying_key, expires_at } => {
+            let key = parse_cosmos_adr36_verifying_key(verifying_key)
+                .map_err(|e| e.context(StatusError::bad_request("Invalid verifying key")))?;
+            validate_key_expiry(expires_at)?;
+            let user_id = ensure_account(app, simulation, &id).await?;
+            Ok(PreparedOperation::AddKey { user_id, key, expires_at })
+        }
+        BatchOperation::AddData { id, data, type_tag, content_type, schema } => {
+            let data = NewData::with_defaults(data, type_tag, content_type, schema);
+            validate_new_data(app.clone(), &data)?;
+            let user_id = ensure_account(app.clone(), simulation, &id).await?;
+
+            let added = simulation.added_data.entry(user_id.clone()).or_default();
+            let stored = app.db.get_data(user_id.clone());
+            let live: Vec<&DataRecord> =
+                stored.iter().filter(|record| !record.is_deleted()).chain(added.iter()).collect();
+            check_data_quota(&app.config.limits, &live, data.value.len())?;
+            added.push(DataRecord::raw(added.len(), data.value.clone()));
+
+            Ok(PreparedOperation::AddData { user_id, data })
+        }
+    }
+}
+
+// Make sure an account exists on-chain or is created earlier in the batch,
+// returning the id it is stored under
+async fn ensure_account(
+    app: Arc<AppState>,
+    simulation: &mut Simulation,
+    id: &str,
+) -> anyhow::Result<String> {
+    let user_id = lookup_account_id(&app, id);
+    if simulation.accounts.contains_key(&user_id) {
+        return Ok(user_id);
+    }
+    match fetch_prover_account(&app, &user_id).await?.account {
+        Some(account) => {
+            simulation.accounts.insert(user_id.clone(), account);
+            Ok(user_id)
+        }
+        None => Err(StatusError::not_found(format!("Account {} not found", user_id)).into()),
+    }
+}
+
+async fn apply(
+    app: Arc<AppState>,
+    index: usize,
+    prepared: PreparedOperation,
+) -> BatchOperationResult {
+    let result = match prepared {
+        PreparedOperation::CreateAccount(create_tx) => {
+            let user_id = create_tx.user_id.clone();
+            submit_create_account(app, create_tx).await.map(|_| (user_id, None))
+        }
+        PreparedOperation::ExistingAccount(user_id) => Ok((user_id, None)),
+        PreparedOperation::AddKey { user_id, key, expires_at } => {
+            store_key(app, user_id.clone(), &key, expires_at);
+            Ok((user_id, None))
+        }
+        PreparedOperation::AddData { user_id, data } => {
+            store_data(app, user_id.clone(), data, None).map(|record| (user_id, Some(record.id)))
+        }
+    };
+    match result {
+        Ok((id, data_id)) => BatchOperationResult {
+            index,
+            status: BatchStatus::Ok,
+            id: Some(id),
+            data_id,
+            error: None,
+        },
+        Err(e) => failed(index, e),
+    }
+}
+
+fn failed(index: usize, error: anyhow::Error) -> BatchOperationResult {
+    BatchOperationResult {
+        index,
+        status: BatchStatus::Failed,
+        id: None,
+        data_id: None,
+        error: Some(format!("{:#}", error)),
+    }
+}
+
+fn skipped(index: usize) -> BatchOperationResult {
+    let status = BatchStatus::Skipped;
+    BatchOperationResult { index, status, id: None, data_id: None, error: None }
+}
+
+// Result of an atomic batch whose operation at the given index exceeds the
+// rate limits of its endpoint
+fn rate_limited(len: usize, failed_index: usize, route: &str, wait: Duration) -> BatchResult {
+    let results = (0..len)
+        .map(|index| {
+            if index == failed_index {
+                failed(index, rate_limit_error(route, wait).into())
+            } else {
+                skipped(index)
+            }
+        })
+        .collect();
+    BatchResult { applied: false, results }
+}
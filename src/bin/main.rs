@@ -3,7 +3,11 @@ use std::sync::Arc;
 use keystore_rs::{KeyChain, KeyStore};
 use prism_be::app::AppState;
 use prism_be::config::parse_config;
+use prism_be::grpc::run_grpc_server;
+use prism_be::key_expiry::run_key_expiry;
+use prism_be::logging::init_logging;
 use prism_be::ops;
+use prism_be::reconciler::run_reconciler;
 use prism_be::server::run_server;
 use prism_client::SigningKey;
 use prism_da::DataAvailabilityLayer;
@@ -15,14 +19,17 @@ use tokio::spawn;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
-
-    let app_config = parse_config("config.toml").unwrap();
+    let mut app_config = parse_config("config.toml").unwrap();
+    if let Ok(token) = std::env::var("PRISM_BE_ADMIN_TOKEN") {
+        app_config.admin.token = Some(token);
+    }
+    let tracer_provider = init_logging(&app_config.logging, &app_config.telemetry).unwrap();
 
     tracing::info!("App config: {:?}", app_config);
 
     let db = InMemoryDatabase::new();
     let (da_layer, _, _) = InMemoryDataAvailabilityLayer::new(3);
+    let da_layer = Arc::new(da_layer) as Arc<dyn DataAvailabilityLayer>;
 
     let keystore_sk = KeyChain
         .get_or_create_signing_key(&app_config.service_id)
@@ -40,22 +47,28 @@ async fn main() {
         start_height: 1,
     };
 
-    let prover = Arc::new(
-        Prover::new(
-            Arc::new(Box::new(db)),
-            Arc::new(da_layer) as Arc<dyn DataAvailabilityLayer>,
-            &cfg,
-        )
-        .unwrap(),
-    );
+    let prover = Arc::new(Prover::new(Arc::new(Box::new(db)), da_layer.clone(), &cfg).unwrap());
 
-    let state = Arc::new(AppState::new(prover.clone(), app_config.service_id.clone(), service_sk));
+    let state = Arc::new(AppState::new(prover.clone(), da_layer, &app_config, service_sk));
+    let reconciler_enabled = app_config.reconciler.enabled;
+    let key_expiry_enabled = app_config.key_expiry.enabled;
+
+    if app_config.grpc.enabled {
+        let (state, config) = (state.clone(), app_config.clone());
+        spawn(async move {
+            if let Err(e) = run_grpc_server(state, config).await {
+                tracing::error!("gRPC server failed: {:?}", e);
+            }
+        });
+    }
 
     let state_clone = state.clone();
 
     let server_handle = spawn(async move {
         tracing::info!("Starting server...");
-        run_server(state_clone, app_config).await;
+        if let Err(e) = run_server(state_clone, app_config).await {
+            tracing::error!("Server failed: {:?}", e);
+        }
     });
 
     let runner = prover.clone();
@@ -66,7 +79,14 @@ async fn main() {
         }
     });
 
-    ops::register_service(state).await.unwrap();
+    ops::register_service(state.clone()).await.unwrap();
+
+    if key_expiry_enabled {
+        spawn(run_key_expiry(state.clone()));
+    }
+    if reconciler_enabled {
+        spawn(run_reconciler(state));
+    }
 
     tokio::select! {
         _ = runner_handle => {
@@ -76,4 +96,9 @@ async fn main() {
             println!("Server task completed");
         }
     }
+
+    // Flush the spans still buffered by the exporter
+    if let Err(e) = tracer_provider.map(|provider| provider.shutdown()).transpose() {
+        tracing::error!("Failed to shut down trace export: {:?}", e);
+    }
 }
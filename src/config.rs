@@ -1,19 +1,206 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AppConfig {
     pub service_id: String,
     pub server: ServerConfig,
+    #[serde(default)]
+    pub account_id: AccountIdConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub reconciler: ReconcilerConfig,
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub recovery: RecoveryConfig,
+    #[serde(default)]
+    pub key_expiry: KeyExpiryConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
 }
 
+// Policy applied to account ids on every account creation path
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccountIdConfig {
+    pub min_length: usize,
+    pub max_length: usize,
+    // Characters allowed besides ASCII letters and digits
+    pub allowed_symbols: String,
+    // Lowercase ids before validating and storing them
+    pub lowercase: bool,
+    // Optional structural format the id must follow
+    pub format: Option<AccountIdFormat>,
+    // Ids that can never be registered, in addition to the service id.
+    // More can be added at runtime through the admin endpoints.
+    pub reserved: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountIdFormat {
+    Email,
+    Domain,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    // Maximum number of transactions queued to the prover and not yet seen
+    // on-chain, across all accounts
+    pub max_pending_transactions: usize,
+    // Limits keyed by route path, e.g. "/v1/account/request-create"
+    pub routes: HashMap<String, RouteRateLimit>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RouteRateLimit {
+    // Bucket shared by all requests from the same client IP
+    pub per_ip: Option<BucketConfig>,
+    // Bucket shared by all requests targeting the same account id
+    pub per_account: Option<BucketConfig>,
+}
+
+// Token bucket holding up to `capacity` requests, refilled continuously at
+// `refill_per_second`
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct BucketConfig {
+    pub capacity: u32,
+    pub refill_per_second: f64,
+}
+
+// Size limits on requests and stored account data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    // Maximum size of any request body
+    pub max_body_bytes: usize,
+    // Maximum size of a single data entry
+    pub max_data_item_bytes: usize,
+    // Maximum number of off-chain data entries per account
+    pub max_data_items_per_account: usize,
+    // Maximum total size of the off-chain data entries of an account
+    pub max_data_bytes_per_account: usize,
+    // Maximum number of operations in a batch request
+    pub max_batch_operations: usize,
+    // Maximum lifetime of a session key
+    pub max_session_secs: u64,
+}
+
+// Background reconciliation between the off-chain database and the prover
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReconcilerConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    // First DA height scanned for accounts on startup
+    pub start_height: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    // How long the response to a request with an idempotency key is replayed
+    pub window_secs: u64,
+    // Maximum number of stored keys, the oldest are dropped beyond it
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    // Filter directives, e.g. "info,prism_prover=warn". RUST_LOG takes
+    // precedence when set.
+    pub level: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+// Export of traces to an OpenTelemetry collector over OTLP/HTTP
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    pub enabled: bool,
+    // Traces endpoint of the collector
+    pub endpoint: String,
+    pub service_name: String,
+    // Fraction of the traces started by this service that are exported.
+    // Traces started by a caller follow the caller's sampling decision.
+    pub sample_ratio: f64,
+}
+
+// Social recovery of accounts through guardian accounts
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RecoveryConfig {
+    // Delay between a recovery reaching its threshold of guardian approvals
+    // and the new key being added, during which the account can cancel it
+    pub timelock_secs: u64,
+    // Time a recovery has to reach its threshold after being started, before
+    // it expires
+    pub approval_window_secs: u64,
+    pub max_guardians: usize,
+}
+
+// Background revocation of expired keys
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KeyExpiryConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+}
+
+// Access to the admin routes
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    // Bearer token admin requests have to carry. The admin routes are
+    // disabled when it is not set. The PRISM_BE_ADMIN_TOKEN environment
+    // variable takes precedence.
+    pub token: Option<String>,
+}
+
+// The config is logged at startup, so the token is left out
+impl std::fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdminConfig").field("token", &self.token.as_ref().map(|_| "***")).finish()
+    }
+}
+
+// gRPC interface served alongside the HTTP API. It is off by default, since
+// HTTP middleware such as rate limits and idempotency keys does not apply to it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
 // Parse the config file from the given path
 // Returns the config if successful, otherwise returns an error
 pub fn parse_config<P: AsRef<Path>>(path: P) -> anyhow::Result<AppConfig> {
@@ -26,6 +213,117 @@ impl Default for AppConfig {
     fn default() -> Self {
         let service_id = "prism-be-id".to_string();
         let server = ServerConfig { port: 8080 };
-        Self { service_id, server }
+        Self {
+            service_id,
+            server,
+            account_id: AccountIdConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            limits: LimitsConfig::default(),
+            reconciler: ReconcilerConfig::default(),
+            idempotency: IdempotencyConfig::default(),
+            grpc: GrpcConfig::default(),
+            logging: LoggingConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            recovery: RecoveryConfig::default(),
+            key_expiry: KeyExpiryConfig::default(),
+            admin: AdminConfig::default(),
+        }
+    }
+}
+
+impl Default for AccountIdConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 3,
+            max_length: 128,
+            allowed_symbols: "-_.@".to_string(),
+            lowercase: false,
+            format: None,
+            reserved: vec!["admin".to_string(), "root".to_string(), "prism".to_string()],
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let per_ip = BucketConfig { capacity: 20, refill_per_second: 1.0 };
+        let per_account = BucketConfig { capacity: 10, refill_per_second: 0.5 };
+        let limited = RouteRateLimit { per_ip: Some(per_ip), per_account: Some(per_account) };
+        let per_ip_only = RouteRateLimit { per_ip: Some(per_ip), per_account: None };
+        let routes = [
+            "/v1/account/request-create",
+            "/v1/account/send-create",
+            "/v1/account/add-key",
+            "/v1/account/add-data",
+        ]
+        .into_iter()
+        .map(|route| (route.to_string(), limited.clone()))
+        .chain([("/v1/batch".to_string(), per_ip_only)])
+        .collect();
+        Self { enabled: true, max_pending_transactions: 1000, routes }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 64 * 1024,
+            max_data_item_bytes: 4 * 1024,
+            max_data_items_per_account: 100,
+            max_data_bytes_per_account: 64 * 1024,
+            max_batch_operations: 100,
+            max_session_secs: 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl Default for ReconcilerConfig {
+    fn default() -> Self {
+        Self { enabled: true, interval_secs: 60, start_height: 1 }
+    }
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self { window_secs: 24 * 60 * 60, max_entries: 10_000 }
+    }
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self { enabled: false, port: 50051 }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self { format: LogFormat::Text, level: "info".to_string() }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "prism-be".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            timelock_secs: 2 * 24 * 60 * 60,
+            approval_window_secs: 7 * 24 * 60 * 60,
+            max_guardians: 10,
+        }
+    }
+}
+
+impl Default for KeyExpiryConfig {
+    fn default() -> Self {
+        Self { enabled: true, interval_secs: 60 }
     }
 }
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use axum::body::{Body, to_bytes};
+use axum::extract::{Request, State};
+use axum::http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, VARY};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+
+use crate::app::AppState;
+
+// Body formats the API can be spoken in. Handlers work on JSON, other formats
+// are translated to and from JSON by the `content_negotiation` middleware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentFormat {
+    Json,
+    Cbor,
+    MessagePack,
+}
+
+impl ContentFormat {
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next().unwrap_or_default().trim();
+        match essence.to_ascii_lowercase().as_str() {
+            "application/json" => Some(ContentFormat::Json),
+            "application/cbor" => Some(ContentFormat::Cbor),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(ContentFormat::MessagePack)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ContentFormat::Json => "application/json",
+            ContentFormat::Cbor => "application/cbor",
+            ContentFormat::MessagePack => "application/msgpack",
+        }
+    }
+
+    // Format of the request body, None if it is not a translated format
+    fn of_request(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        Self::from_media_type(content_type)
+    }
+
+    // Response format preferred by the Accept header, by quality and then by
+    // order. JSON is used when no supported format is listed.
+    pub fn accepted(headers: &HeaderMap) -> Self {
+        let Some(accept) = headers.get(ACCEPT).and_then(|accept| accept.to_str().ok()) else {
+            return ContentFormat::Json;
+        };
+        let mut best: Option<(ContentFormat, f32)> = None;
+        for media_range in accept.split(',') {
+            let Some(format) = Self::from_media_type(media_range) else {
+                continue;
+            };
+            let quality = media_range
+                .split(';')
+                .skip(1)
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+        best.map(|(format, _)| format).unwrap_or(ContentFormat::Json)
+    }
+
+    pub fn decode(self, body: &[u8]) -> anyhow::Result<Value> {
+        Ok(match self {
+            ContentFormat::Json => serde_json::from_slice(body)?,
+            ContentFormat::Cbor => ciborium::from_reader(body)?,
+            ContentFormat::MessagePack => rmp_serde::from_slice(body)?,
+        })
+    }
+
+    pub fn encode(self, value: &Value) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            ContentFormat::Json => serde_json::to_vec(value)?,
+            ContentFormat::Cbor => {
+                let mut body = Vec::new();
+                ciborium::into_writer(value, &mut body)?;
+                body
+            }
+            ContentFormat::MessagePack => rmp_serde::to_vec_named(value)?,
+        })
+    }
+}
+
+// Middleware letting clients send and receive CBOR or MessagePack bodies.
+// Request bodies are translated to JSON before reaching the handlers, and JSON
+// responses are translated to the format preferred by the Accept header.
+pub async fn content_negotiation(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let response_format = ContentFormat::accepted(req.headers());
+    let req = match ContentFormat::of_request(req.headers()) {
+        Some(format) if format != ContentFormat::Json => {
+            match translate_request(req, format, state.config.limits.max_body_bytes).await {
+                Ok(req) => req,
+                Err(response) => return response,
+            }
+        }
+        _ => req,
+    };
+
+    let mut response = next.run(req).await;
+    response.headers_mut().append(VARY, HeaderValue::from_static("accept"));
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(ContentFormat::from_media_type)
+        == Some(ContentFormat::Json);
+    if response_format == ContentFormat::Json || !is_json {
+        return response;
+    }
+    translate_response(response, response_format).await
+}
+
+async fn translate_request(
+    req: Request,
+    format: ContentFormat,
+    limit: usize,
+) -> Result<Request, Response> {
+    let (mut parts, body) = req.into_parts();
+    let body = to_bytes(body, limit).await.map_err(|_| {
+        (StatusCode::PAYLOAD_TOO_LARGE, "Error: Request body too large").into_response()
+    })?;
+    let json = format.decode(&body).and_then(|value| ContentFormat::Json.encode(&value));
+    let json = json.map_err(|e| {
+        let message = format!("Error: Invalid {} body: {}", format.media_type(), e);
+        (StatusCode::BAD_REQUEST, message).into_response()
+    })?;
+    let media_type = HeaderValue::from_static(ContentFormat::Json.media_type());
+    parts.headers.insert(CONTENT_TYPE, media_type);
+    parts.headers.remove(CONTENT_LENGTH);
+    Ok(Request::from_parts(parts, Body::from(json)))
+}
+
+async fn translate_response(response: Response, format: ContentFormat) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let encoded = ContentFormat::Json.decode(&body).and_then(|value| format.encode(&value));
+    let Ok(encoded) = encoded else {
+        // Leave bodies that are not valid JSON untouched
+        return Response::from_parts(parts, Body::from(body));
+    };
+    parts.headers.insert(CONTENT_TYPE, HeaderValue::from_static(format.media_type()));
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(encoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_accepted_format() {
+        let accepted = |accept: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT, HeaderValue::from_str(accept).unwrap());
+            ContentFormat::accepted(&headers)
+        };
+        assert_eq!(ContentFormat::accepted(&HeaderMap::new()), ContentFormat::Json);
+        assert_eq!(accepted("*/*"), ContentFormat::Json);
+        assert_eq!(accepted("application/cbor"), ContentFormat::Cbor);
+        assert_eq!(accepted("application/x-msgpack, application/cbor"), ContentFormat::MessagePack);
+        assert_eq!(accepted("application/json;q=0.5, application/cbor"), ContentFormat::Cbor);
+        assert_eq!(accepted("application/cbor; encoding=hex"), ContentFormat::Cbor);
+        assert_eq!(accepted("application/cbor;q=0"), ContentFormat::Json);
+    }
+
+    #[test]
+    fn test_formats_round_trip() {
+        let value = json!({ "id": "alice", "nonce": 3, "keys": [{ "value": "AAE=" }] });
+        for format in [ContentFormat::Json, ContentFormat::Cbor, ContentFormat::MessagePack] {
+            let encoded = format.encode(&value).unwrap();
+            assert_eq!(format.decode(&encoded).unwrap(), value, "{:?}", format);
+        }
+    }
+}
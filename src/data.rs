@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::app::StatusError;
+
+pub const DEFAULT_DATA_TYPE: &str = "text";
+pub const DEFAULT_CONTENT_TYPE: &str = "text/plain";
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+// Type tag and content type reported for raw on-chain data
+pub const RAW_DATA_TYPE: &str = "raw";
+pub const RAW_CONTENT_TYPE: &str = "application/octet-stream";
+
+// Prefix of the ids given to on-chain data entries, followed by their index
+pub const ONCHAIN_ID_PREFIX: &str = "chain-";
+
+// Data entry stored for an account
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DataRecord {
+    // Stable id of the entry within its account
+    pub id: String,
+    // Incremented on every change, and part of the signed change payload so a
+    // signature cannot be replayed
+    pub revision: u64,
+    // Application defined type of the entry, e.g. "profile.name"
+    #[serde(rename = "type")]
+    pub type_tag: String,
+    pub content_type: String,
+    // Unix timestamp in seconds, unknown for on-chain data
+    pub created_at: Option<u64>,
+    // Id of the registered schema the value was validated against
+    pub schema: Option<String>,
+    pub value: String,
+    pub updated_at: Option<u64>,
+    // Set when the entry was deleted. Deleted entries are kept as tombstones
+    // with an empty value and hidden from account views.
+    pub deleted_at: Option<u64>,
+}
+
+impl DataRecord {
+    // Record for the raw on-chain data entry at the given index, in base64
+    pub fn raw(index: usize, value: String) -> Self {
+        Self {
+            id: format!("{}{}", ONCHAIN_ID_PREFIX, index),
+            revision: 0,
+            type_tag: RAW_DATA_TYPE.to_string(),
+            content_type: RAW_CONTENT_TYPE.to_string(),
+            created_at: None,
+            schema: None,
+            value,
+            updated_at: None,
+            deleted_at: None,
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+// Change to an existing data entry
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DataChange {
+    Replace(NewData),
+    Delete,
+}
+
+// Typed data entry to add to an account, or to replace an entry with
+#[derive(Serialize, Debug, Clone)]
+pub struct NewData {
+    #[serde(rename = "type")]
+    pub type_tag: String,
+    pub content_type: String,
+    pub schema: Option<String>,
+    pub value: String,
+}
+
+impl NewData {
+    // Entry with the defaults of the optional fields of a request filled in.
+    // The content type defaults to JSON when a schema is given.
+    pub fn with_defaults(
+        value: String,
+        type_tag: Option<String>,
+        content_type: Option<String>,
+        schema: Option<String>,
+    ) -> Self {
+        let default_content_type =
+            if schema.is_some() { JSON_CONTENT_TYPE } else { DEFAULT_CONTENT_TYPE };
+        Self {
+            type_tag: type_tag.unwrap_or_else(|| DEFAULT_DATA_TYPE.to_string()),
+            content_type: content_type.unwrap_or_else(|| default_content_type.to_string()),
+            schema,
+            value,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DataChangePayload<'a> {
+    service_id: &'a str,
+    account_id: &'a str,
+    entry_id: &'a str,
+    revision: u64,
+    #[serde(flatten)]
+    change: &'a DataChange,
+}
+
+// Bytes an account key has to sign to authorize a change to one of its data
+// entries at the given revision
+pub fn data_change_payload(
+    service_id: &str,
+    account_id: &str,
+    entry_id: &str,
+    revision: u64,
+    change: &DataChange,
+) -> Vec<u8> {
+    let payload = DataChangePayload { service_id, account_id, entry_id, revision, change };
+    serde_json::to_vec(&payload).expect("data change payload is always serializable")
+}
+
+// Check that a schema document is a valid JSON schema
+pub fn check_schema(schema: &Value) -> Result<(), StatusError> {
+    jsonschema::validator_for(schema)
+        .map(|_| ())
+        .map_err(|e| StatusError::bad_request(format!("Invalid JSON schema: {}", e)))
+}
+
+// Validate a JSON encoded value against a schema document
+pub fn validate_value(schema: &Value, value: &str) -> Result<(), StatusError> {
+    let validator = jsonschema::validator_for(schema)
+        .map_err(|e| StatusError::bad_request(format!("Invalid JSON schema: {}", e)))?;
+    let instance: Value = serde_json::from_str(value)
+        .map_err(|e| StatusError::bad_request(format!("Data is not valid JSON: {}", e)))?;
+    let errors: Vec<String> = validator.iter_errors(&instance).map(|e| e.to_string()).collect();
+    if !errors.is_empty() {
+        let message = format!("Data does not match schema: {}", errors.join("; "));
+        return Err(StatusError::bad_request(message));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_data_change_payload_depends_on_revision() {
+        let change = DataChange::Delete;
+        let first = data_change_payload("service", "alice", "entry", 1, &change);
+        let second = data_change_payload("service", "alice", "entry", 2, &change);
+        assert_ne!(first, second);
+        let payload: Value = serde_json::from_slice(&first).unwrap();
+        assert_eq!(payload["action"], "delete");
+        assert_eq!(payload["entry_id"], "entry");
+    }
+
+    #[test]
+    fn test_validate_value() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "name": { "type": "string" } },
+            "required": ["name"]
+        });
+        assert!(check_schema(&schema).is_ok());
+        assert!(validate_value(&schema, r#"{"name": "alice"}"#).is_ok());
+        assert!(validate_value(&schema, r#"{"name": 1}"#).is_err());
+        assert!(validate_value(&schema, "not json").is_err());
+    }
+}
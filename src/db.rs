@@ -1,7 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Mutex;
 
 use prism_client::Account;
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::app::StatusError;
+use crate::data::DataRecord;
+use crate::history::AccountEvent;
+use crate::key_expiry::PendingRevocation;
+use crate::key_metadata::KeyMetadata;
+use crate::reconciler::DriftReport;
+use crate::recovery::{RecoveryRequest, RecoverySetup, RecoveryStatus};
+use crate::session::SessionKey;
+
+// Number of events buffered for subscribers that fall behind
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
 
 // In memory database for storing data in application
 pub struct Database {
@@ -11,8 +25,75 @@ pub struct Database {
     // Map of user id to keys
     pub keys: Mutex<HashMap<String, Vec<String>>>,
 
+    // Map of user id to the metadata of the account's keys, by key
+    pub key_metadata: Mutex<HashMap<String, HashMap<String, KeyMetadata>>>,
+
+    // Map of user id to expired on-chain keys waiting for a revoke transaction
+    pub pending_revocations: Mutex<HashMap<String, Vec<PendingRevocation>>>,
+
     // Map of user id to data
-    pub data: Mutex<HashMap<String, Vec<String>>>,
+    pub data: Mutex<HashMap<String, Vec<DataRecord>>>,
+
+    // Map of user id to keys submitted to the prover but not yet seen on-chain
+    pub pending_keys: Mutex<HashMap<String, Vec<String>>>,
+
+    // Accounts by verifying key, covering both on-chain and off-chain keys
+    pub key_index: Mutex<KeyIndex>,
+
+    // Lowercased account ids that cannot be registered
+    pub reserved_ids: Mutex<BTreeSet<String>>,
+
+    // Map of schema id to the JSON schema registered by the service
+    // and its version
+    pub schemas: Mutex<BTreeMap<String, (u64, Value)>>,
+
+    // Map of user id to the append-only event log of the account
+    pub events: Mutex<HashMap<String, Vec<AccountEvent>>>,
+
+    // Map of user id to the session keys authorized by the account, including
+    // expired and revoked ones
+    pub sessions: Mutex<HashMap<String, Vec<SessionKey>>>,
+
+    // Map of user id to the guardians set up to recover the account
+    pub recovery_setups: Mutex<HashMap<String, RecoverySetup>>,
+
+    // Map of user id to the recoveries started for the account
+    pub recoveries: Mutex<HashMap<String, Vec<RecoveryRequest>>>,
+
+    // Every appended event with the id of its account, for live subscribers
+    pub event_tx: broadcast::Sender<(String, AccountEvent)>,
+
+    // Last DA height scanned by the reconciler
+    pub reconciled_height: Mutex<u64>,
+
+    // Result of the last reconciliation run
+    pub drift_report: Mutex<Option<DriftReport>>,
+}
+
+// Reverse index of the keys of accounts, with the keys indexed for each
+// account so they can be updated without scanning the whole index
+#[derive(Default)]
+pub struct KeyIndex {
+    // Map of verifying key to the ids of the accounts holding it
+    accounts_by_key: HashMap<String, BTreeSet<String>>,
+    // Map of user id to the keys indexed for the account
+    keys_by_account: HashMap<String, BTreeSet<String>>,
+}
+
+impl KeyIndex {
+    fn insert(&mut self, id: String, key: String) {
+        self.accounts_by_key.entry(key.clone()).or_default().insert(id.clone());
+        self.keys_by_account.entry(id).or_default().insert(key);
+    }
+
+    fn remove(&mut self, id: &str, key: &str) {
+        if let Some(ids) = self.accounts_by_key.get_mut(key) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.accounts_by_key.remove(key);
+            }
+        }
+    }
 }
 
 impl Default for Database {
@@ -26,12 +107,28 @@ impl Database {
         Self {
             accounts: Mutex::new(HashMap::new()),
             keys: Mutex::new(HashMap::new()),
+            key_metadata: Mutex::new(HashMap::new()),
+            pending_revocations: Mutex::new(HashMap::new()),
             data: Mutex::new(HashMap::new()),
+            pending_keys: Mutex::new(HashMap::new()),
+            key_index: Mutex::new(KeyIndex::default()),
+            reserved_ids: Mutex::new(BTreeSet::new()),
+            schemas: Mutex::new(BTreeMap::new()),
+            events: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+            recovery_setups: Mutex::new(HashMap::new()),
+            recoveries: Mutex::new(HashMap::new()),
+            event_tx: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            reconciled_height: Mutex::new(0),
+            drift_report: Mutex::new(None),
         }
     }
 
+    // Ids of all known accounts, in lexicographic order
     pub fn get_accounts(&self) -> Vec<String> {
-        self.accounts.lock().unwrap().keys().cloned().collect()
+        let mut ids: Vec<String> = self.accounts.lock().unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
     }
 
     pub fn get_keys(&self, id: String) -> Vec<String> {
@@ -42,19 +139,465 @@ impl Database {
         self.accounts.lock().unwrap().insert(id, account);
     }
 
+    // Off-chain keys are not indexed, anyone can add them to any account
     pub fn insert_key(&self, id: String, key: String) {
         self.keys.lock().unwrap().entry(id).or_default().push(key);
     }
 
-    pub fn insert_data(&self, id: String, data: String) {
+    // Remove an off-chain key of an account, returning whether it was stored
+    pub fn remove_key(&self, id: String, key: String) -> bool {
+        let mut keys = self.keys.lock().unwrap();
+        let Some(account_keys) = keys.get_mut(&id) else {
+            return false;
+        };
+        let len = account_keys.len();
+        account_keys.retain(|existing| existing != &key);
+        account_keys.len() < len
+    }
+
+    // Apply a change to the metadata of a key, starting from empty metadata
+    // for keys without any, and return the updated metadata
+    pub fn update_key_metadata<F>(&self, id: String, key: String, change: F) -> KeyMetadata
+    where
+        F: FnOnce(&mut KeyMetadata),
+    {
+        let mut key_metadata = self.key_metadata.lock().unwrap();
+        let metadata = key_metadata.entry(id).or_default().entry(key).or_default();
+        change(metadata);
+        metadata.clone()
+    }
+
+    pub fn get_key_metadata(&self, id: String) -> HashMap<String, KeyMetadata> {
+        self.key_metadata.lock().unwrap().get(&id).cloned().unwrap_or_default()
+    }
+
+    pub fn remove_key_metadata(&self, id: String, key: &str) {
+        if let Some(account_metadata) = self.key_metadata.lock().unwrap().get_mut(&id) {
+            account_metadata.remove(key);
+        }
+    }
+
+    // Keys that expired at the given time, with their expiry, by user id
+    pub fn get_expired_keys(&self, now: u64) -> BTreeMap<String, Vec<(String, u64)>> {
+        let key_metadata = self.key_metadata.lock().unwrap();
+        let mut expired: BTreeMap<String, Vec<(String, u64)>> = BTreeMap::new();
+        for (id, account_metadata) in key_metadata.iter() {
+            for (key, metadata) in account_metadata {
+                if let Some(expires_at) = metadata.expires_at.filter(|_| metadata.is_expired(now)) {
+                    expired.entry(id.clone()).or_default().push((key.clone(), expires_at));
+                }
+            }
+        }
+        expired
+    }
+
+    pub fn queue_revocation(&self, id: String, revocation: PendingRevocation) {
+        self.pending_revocations.lock().unwrap().entry(id).or_default().push(revocation);
+    }
+
+    pub fn get_pending_revocations(&self, id: String) -> Vec<PendingRevocation> {
+        self.pending_revocations.lock().unwrap().get(&id).cloned().unwrap_or_default()
+    }
+
+    // Record the transaction submitted to revoke an expired key, returning
+    // the updated revocation, or None if the key is not queued
+    pub fn set_revocation_tx(
+        &self,
+        id: String,
+        key: &str,
+        tx_id: String,
+    ) -> Option<PendingRevocation> {
+        let mut pending_revocations = self.pending_revocations.lock().unwrap();
+        let revocation = pending_revocations.get_mut(&id)?.iter_mut().find(|r| r.key == key)?;
+        revocation.tx_id = Some(tx_id);
+        Some(revocation.clone())
+    }
+
+    pub fn remove_pending_revocation(&self, id: String, key: &str) {
+        if let Some(revocations) = self.pending_revocations.lock().unwrap().get_mut(&id) {
+            revocations.retain(|revocation| revocation.key != key);
+        }
+    }
+
+    pub fn insert_data(&self, id: String, data: DataRecord) {
         self.data.lock().unwrap().entry(id).or_default().push(data);
     }
 
-    pub fn get_data(&self, id: String) -> Vec<String> {
+    // Insert data for an account if the given check accepts the data already
+    // stored for it. The check runs under the same lock as the insertion.
+    pub fn try_insert_data<F>(&self, id: String, data: DataRecord, check: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&[DataRecord]) -> anyhow::Result<()>,
+    {
+        let mut all_data = self.data.lock().unwrap();
+        let account_data = all_data.entry(id).or_default();
+        check(account_data)?;
+        account_data.push(data);
+        Ok(())
+    }
+
+    // Apply a change to the data entry with the given entry id under the data
+    // lock, returning the updated entry
+    pub fn update_data<F>(
+        &self,
+        id: String,
+        entry_id: &str,
+        change: F,
+    ) -> anyhow::Result<DataRecord>
+    where
+        F: FnOnce(&mut DataRecord, &[DataRecord]) -> anyhow::Result<()>,
+    {
+        let mut all_data = self.data.lock().unwrap();
+        let account_data = all_data.entry(id).or_default();
+        let position = account_data
+            .iter()
+            .position(|record| record.id == entry_id)
+            .ok_or_else(|| StatusError::not_found(format!("Data entry {} not found", entry_id)))?;
+        let mut record = account_data[position].clone();
+        change(&mut record, account_data)?;
+        account_data[position] = record.clone();
+        Ok(record)
+    }
+
+    pub fn get_data(&self, id: String) -> Vec<DataRecord> {
         self.data.lock().unwrap().get(&id).cloned().unwrap_or_default()
     }
 
     pub fn get_key(&self, id: String) -> Vec<String> {
         self.keys.lock().unwrap().get(&id).cloned().unwrap_or_default()
     }
+
+    pub fn insert_pending_key(&self, id: String, key: String) {
+        self.index_key(id.clone(), key.clone());
+        self.pending_keys.lock().unwrap().entry(id).or_default().push(key);
+    }
+
+    pub fn get_pending_keys(&self, id: String) -> Vec<String> {
+        self.pending_keys.lock().unwrap().get(&id).cloned().unwrap_or_default()
+    }
+
+    // Number of transactions queued to the prover and not yet seen on-chain
+    // across all accounts: pending keys, which include the keys added by
+    // creations and recoveries, and submitted revocations of expired keys
+    pub fn pending_count(&self) -> usize {
+        let keys: usize = self.pending_keys.lock().unwrap().values().map(Vec::len).sum();
+        let revocations = self.pending_revocations.lock().unwrap();
+        let revocations = revocations.values().flatten().filter(|r| r.tx_id.is_some()).count();
+        keys + revocations
+    }
+
+    // Ids of the accounts with pending keys or submitted revocations
+    pub fn get_pending_accounts(&self) -> Vec<String> {
+        let mut ids = BTreeSet::new();
+        for (id, keys) in self.pending_keys.lock().unwrap().iter() {
+            if !keys.is_empty() {
+                ids.insert(id.clone());
+            }
+        }
+        for (id, revocations) in self.pending_revocations.lock().unwrap().iter() {
+            if revocations.iter().any(|revocation| revocation.tx_id.is_some()) {
+                ids.insert(id.clone());
+            }
+        }
+        ids.into_iter().collect()
+    }
+
+    // Drop pending keys of an account that have been seen on-chain, and
+    // submitted revocations whose key is no longer on-chain
+    pub fn clear_included(&self, id: String, onchain_keys: &[String]) {
+        if let Some(keys) = self.pending_keys.lock().unwrap().get_mut(&id) {
+            keys.retain(|key| !onchain_keys.contains(key));
+        }
+        if let Some(revocations) = self.pending_revocations.lock().unwrap().get_mut(&id) {
+            revocations.retain(|revocation| {
+                revocation.tx_id.is_none() || onchain_keys.contains(&revocation.key)
+            });
+        }
+    }
+
+    // Record that the account with the given id holds the given key
+    pub fn index_key(&self, id: String, key: String) {
+        self.key_index.lock().unwrap().insert(id, key);
+    }
+
+    // Replace the indexed keys of an account with the given set, dropping
+    // entries for keys the account no longer holds. Only the entries of the
+    // account's old and new keys are touched.
+    pub fn reindex_keys(&self, id: String, keys: Vec<String>) {
+        let mut index = self.key_index.lock().unwrap();
+        let keys: BTreeSet<String> = keys.into_iter().collect();
+        let old_keys = index.keys_by_account.remove(&id).unwrap_or_default();
+        for key in old_keys.difference(&keys) {
+            index.remove(&id, key);
+        }
+        for key in keys {
+            index.insert(id.clone(), key);
+        }
+    }
+
+    // Ids of the accounts holding the given key, in lexicographic order
+    pub fn get_accounts_by_key(&self, key: String) -> Vec<String> {
+        let index = self.key_index.lock().unwrap();
+        index.accounts_by_key.get(&key).map(|ids| ids.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn insert_reserved_ids(&self, ids: Vec<String>) {
+        let mut reserved = self.reserved_ids.lock().unwrap();
+        reserved.extend(ids.into_iter().map(|id| id.to_lowercase()));
+    }
+
+    // Remove a reserved id, returning whether it was reserved
+    pub fn remove_reserved_id(&self, id: String) -> bool {
+        self.reserved_ids.lock().unwrap().remove(&id.to_lowercase())
+    }
+
+    pub fn get_reserved_ids(&self) -> Vec<String> {
+        self.reserved_ids.lock().unwrap().iter().cloned().collect()
+    }
+
+    // Store a schema at the given version if it directly follows the current
+    // version of the id, 0 for a new id. Returns the current version otherwise.
+    pub fn insert_schema(&self, id: String, version: u64, schema: Value) -> Result<(), u64> {
+        let mut schemas = self.schemas.lock().unwrap();
+        let current = schemas.get(&id).map_or(0, |(version, _)| *version);
+        if version != current + 1 {
+            return Err(current);
+        }
+        schemas.insert(id, (version, schema));
+        Ok(())
+    }
+
+    // Current version of a schema and the schema itself
+    pub fn get_schema(&self, id: String) -> Option<(u64, Value)> {
+        self.schemas.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn get_schemas(&self) -> Vec<(String, u64, Value)> {
+        let schemas = self.schemas.lock().unwrap();
+        schemas
+            .iter()
+            .map(|(id, (version, schema))| (id.clone(), *version, schema.clone()))
+            .collect()
+    }
+
+    // Store a session key, unless the account already authorized the same key
+    // before. Returns whether the key was stored.
+    pub fn insert_session(&self, id: String, session: SessionKey) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let account_sessions = sessions.entry(id).or_default();
+        if account_sessions.iter().any(|s| s.verifying_key == session.verifying_key) {
+            return false;
+        }
+        account_sessions.push(session);
+        true
+    }
+
+    pub fn get_sessions(&self, id: String) -> Vec<SessionKey> {
+        self.sessions.lock().unwrap().get(&id).cloned().unwrap_or_default()
+    }
+
+    // Apply a change to a session key of an account under the sessions lock,
+    // returning the updated session, or None if the account has no such key
+    pub fn update_session<F>(
+        &self,
+        id: String,
+        key: &str,
+        change: F,
+    ) -> Option<anyhow::Result<SessionKey>>
+    where
+        F: FnOnce(&mut SessionKey) -> anyhow::Result<()>,
+    {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(&id)?.iter_mut().find(|s| s.verifying_key == key)?;
+        let mut updated = session.clone();
+        Some(change(&mut updated).map(|()| {
+            *session = updated.clone();
+            updated
+        }))
+    }
+
+    pub fn set_recovery_setup(&self, id: String, setup: RecoverySetup) {
+        self.recovery_setups.lock().unwrap().insert(id, setup);
+    }
+
+    pub fn get_recovery_setup(&self, id: String) -> Option<RecoverySetup> {
+        self.recovery_setups.lock().unwrap().get(&id).cloned()
+    }
+
+    // Store a recovery started after `sequence` earlier recoveries of the
+    // account, replacing pending recoveries that have not reached their
+    // threshold. Returns the ids of the replaced recoveries, or None if
+    // another recovery was started since or an approved one is pending.
+    pub fn insert_recovery(
+        &self,
+        id: String,
+        recovery: RecoveryRequest,
+        sequence: usize,
+    ) -> Option<Vec<String>> {
+        let mut recoveries = self.recoveries.lock().unwrap();
+        let account_recoveries = recoveries.entry(id).or_default();
+        if account_recoveries.len() != sequence
+            || account_recoveries
+                .iter()
+                .any(|r| r.status == RecoveryStatus::Pending && r.executable_at.is_some())
+        {
+            return None;
+        }
+        let mut replaced = Vec::new();
+        for pending in account_recoveries.iter_mut().filter(|r| r.is_unapproved()) {
+            pending.status = RecoveryStatus::Replaced;
+            pending.updated_at = recovery.created_at;
+            replaced.push(pending.id.clone());
+        }
+        account_recoveries.push(recovery);
+        Some(replaced)
+    }
+
+    // Mark the pending recoveries of an account whose approval window ended
+    // as expired
+    pub fn expire_recoveries(&self, id: String, now: u64) {
+        let mut recoveries = self.recoveries.lock().unwrap();
+        let Some(account_recoveries) = recoveries.get_mut(&id) else {
+            return;
+        };
+        for recovery in account_recoveries.iter_mut().filter(|r| r.is_expired(now)) {
+            recovery.status = RecoveryStatus::Expired;
+            recovery.updated_at = now;
+        }
+    }
+
+    pub fn get_recoveries(&self, id: String) -> Vec<RecoveryRequest> {
+        self.recoveries.lock().unwrap().get(&id).cloned().unwrap_or_default()
+    }
+
+    // Apply a change to a recovery of an account under the recoveries lock,
+    // returning the updated recovery, or None if the account has no such
+    // recovery
+    pub fn update_recovery<F>(
+        &self,
+        id: String,
+        recovery_id: &str,
+        change: F,
+    ) -> Option<anyhow::Result<RecoveryRequest>>
+    where
+        F: FnOnce(&mut RecoveryRequest) -> anyhow::Result<()>,
+    {
+        let mut recoveries = self.recoveries.lock().unwrap();
+        let recovery = recoveries.get_mut(&id)?.iter_mut().find(|r| r.id == recovery_id)?;
+        let mut updated = recovery.clone();
+        Some(change(&mut updated).map(|()| {
+            *recovery = updated.clone();
+            updated
+        }))
+    }
+
+    // Append an event to the log of an account, assigning its sequence number
+    pub fn append_event(&self, id: String, mut event: AccountEvent) -> AccountEvent {
+        let mut events = self.events.lock().unwrap();
+        let log = events.entry(id.clone()).or_default();
+        event.seq = log.len() as u64;
+        log.push(event.clone());
+        // Sending only fails when nobody is subscribed
+        let _ = self.event_tx.send((id, event.clone()));
+        event
+    }
+
+    // Receive the events appended from now on
+    pub fn subscribe_events(&self) -> broadcast::Receiver<(String, AccountEvent)> {
+        self.event_tx.subscribe()
+    }
+
+    // Up to `limit` events of an account starting at sequence number `from`,
+    // and whether more events follow
+    pub fn get_events(&self, id: String, from: u64, limit: usize) -> (Vec<AccountEvent>, bool) {
+        let events = self.events.lock().unwrap();
+        let log = events.get(&id).map(Vec::as_slice).unwrap_or_default();
+        let start = usize::try_from(from).unwrap_or(usize::MAX).min(log.len());
+        let end = start.saturating_add(limit).min(log.len());
+        (log[start..end].to_vec(), end < log.len())
+    }
+
+    // Record the epoch at which a transaction of an account was observed
+    // on-chain on the event that submitted it, if it does not have one yet
+    pub fn set_event_epoch(&self, id: &str, tx_id: &str, epoch: u64) {
+        let mut events = self.events.lock().unwrap();
+        let Some(log) = events.get_mut(id) else {
+            return;
+        };
+        if let Some(event) = log.iter_mut().find(|event| event.tx_id.as_deref() == Some(tx_id)) {
+            event.epoch.get_or_insert(epoch);
+        }
+    }
+
+    pub fn set_drift_report(&self, report: DriftReport) {
+        *self.drift_report.lock().unwrap() = Some(report);
+    }
+
+    pub fn get_drift_report(&self) -> Option<DriftReport> {
+        self.drift_report.lock().unwrap().clone()
+    }
+
+    pub fn set_reconciled_height(&self, height: u64) {
+        *self.reconciled_height.lock().unwrap() = height;
+    }
+
+    pub fn get_reconciled_height(&self) -> u64 {
+        *self.reconciled_height.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::EventKind;
+
+    #[test]
+    fn test_reindex_keys_only_touches_the_account() {
+        let db = Database::new();
+        db.index_key("alice".to_string(), "a".to_string());
+        db.index_key("bob".to_string(), "a".to_string());
+        db.reindex_keys("alice".to_string(), vec!["b".to_string()]);
+
+        assert_eq!(db.get_accounts_by_key("a".to_string()), vec!["bob".to_string()]);
+        assert_eq!(db.get_accounts_by_key("b".to_string()), vec!["alice".to_string()]);
+        db.reindex_keys("bob".to_string(), vec![]);
+        // Keys no account holds anymore are dropped from the index
+        assert!(!db.key_index.lock().unwrap().accounts_by_key.contains_key("a"));
+    }
+
+    #[test]
+    fn test_pending_count_includes_submitted_revocations() {
+        let db = Database::new();
+        db.insert_pending_key("alice".to_string(), "a".to_string());
+        let revocation = |key: &str| PendingRevocation {
+            key: key.to_string(),
+            expired_at: 0,
+            tx_id: None,
+        };
+        db.queue_revocation("bob".to_string(), revocation("b"));
+        db.queue_revocation("bob".to_string(), revocation("c"));
+        // Revocations waiting for a signature are not queued to the prover yet
+        assert_eq!(db.pending_count(), 1);
+        db.set_revocation_tx("bob".to_string(), "b", "tx".to_string());
+        assert_eq!(db.pending_count(), 2);
+        assert_eq!(db.get_pending_accounts(), vec!["alice".to_string(), "bob".to_string()]);
+
+        db.clear_included("bob".to_string(), &["c".to_string()]);
+        assert_eq!(db.pending_count(), 1);
+        assert_eq!(db.get_pending_revocations("bob".to_string()).len(), 1);
+    }
+
+    #[test]
+    fn test_set_event_epoch_matches_the_transaction() {
+        let db = Database::new();
+        let event = |tx_id: &str| AccountEvent::new(EventKind::KeyAdded).with_tx_id(tx_id.into());
+        db.append_event("alice".to_string(), event("first"));
+        db.append_event("alice".to_string(), event("second"));
+        db.set_event_epoch("alice", "second", 7);
+        db.set_event_epoch("alice", "second", 9);
+
+        let epochs: Vec<Option<u64>> =
+            db.events.lock().unwrap()["alice"].iter().map(|event| event.epoch).collect();
+        assert_eq!(epochs, vec![None, Some(7)]);
+    }
 }
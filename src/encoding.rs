@@ -0,0 +1,245 @@
+use anyhow::anyhow;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::header::ACCEPT;
+use axum::http::request::Parts;
+use prism_serde::base64::{FromBase64, ToBase64};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{AppError, StatusError};
+use crate::data::{DataRecord, RAW_DATA_TYPE};
+use crate::history::AccountEvent;
+use crate::key_expiry::PendingRevocation;
+use crate::key_metadata::KeyMetadata;
+use crate::ops::{AccountView, HistoryPage, SourcedData, SourcedValue};
+use crate::reconciler::{AccountDrift, DriftReport};
+use crate::recovery::{AccountRecovery, GuardianApproval, RecoveryRequest, ServiceKeyGrant};
+use crate::session::SessionKey;
+
+// Encoding of the binary values of API responses: signing payloads, verifying
+// keys, transaction hashes and raw on-chain data. Binary values are stored in
+// base64 and re-encoded when a response asks for another encoding.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryEncoding {
+    #[default]
+    Base64,
+    Hex,
+}
+
+impl BinaryEncoding {
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            BinaryEncoding::Base64 => bytes.to_base64(),
+            BinaryEncoding::Hex => hex::encode(bytes),
+        }
+    }
+
+    pub fn decode(self, value: &str) -> anyhow::Result<Vec<u8>> {
+        match self {
+            BinaryEncoding::Base64 => {
+                Vec::<u8>::from_base64(value).map_err(|e| anyhow!("Invalid base64: {}", e))
+            }
+            BinaryEncoding::Hex => Ok(hex::decode(value)?),
+        }
+    }
+
+    // Re-encode a stored base64 value in this encoding. Stored binary values
+    // are always base64, so anything else is an error rather than passed on.
+    pub fn reencode(self, base64: &str) -> anyhow::Result<String> {
+        let bytes = BinaryEncoding::Base64.decode(base64)?;
+        Ok(match self {
+            BinaryEncoding::Base64 => base64.to_string(),
+            BinaryEncoding::Hex => hex::encode(bytes),
+        })
+    }
+
+    fn parse(value: &str) -> Result<Self, StatusError> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "base64" => Ok(BinaryEncoding::Base64),
+            "hex" => Ok(BinaryEncoding::Hex),
+            other => Err(StatusError::bad_request(format!("Unsupported encoding {}", other))),
+        }
+    }
+
+    pub fn encode_keys(self, keys: Vec<SourcedValue>) -> anyhow::Result<Vec<SourcedValue>> {
+        keys.into_iter()
+            .map(|key| {
+                Ok(SourcedValue {
+                    value: self.reencode(&key.value)?,
+                    source: key.source,
+                    metadata: self.encode_key_metadata(key.metadata)?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn encode_key_metadata(self, metadata: KeyMetadata) -> anyhow::Result<KeyMetadata> {
+        let added_by = self.reencode_option(metadata.added_by)?;
+        Ok(KeyMetadata { added_by, ..metadata })
+    }
+
+    // Raw on-chain data is binary, typed off-chain data is returned as stored
+    pub fn encode_data(self, data: Vec<SourcedData>) -> anyhow::Result<Vec<SourcedData>> {
+        data.into_iter()
+            .map(|data| Ok(SourcedData { record: self.encode_record(data.record)?, ..data }))
+            .collect()
+    }
+
+    pub fn encode_record(self, mut record: DataRecord) -> anyhow::Result<DataRecord> {
+        if record.type_tag == RAW_DATA_TYPE {
+            record.value = self.reencode(&record.value)?;
+        }
+        Ok(record)
+    }
+
+    pub fn encode_view(self, view: AccountView) -> anyhow::Result<AccountView> {
+        Ok(AccountView {
+            keys: self.encode_keys(view.keys)?,
+            data: self.encode_data(view.data)?,
+            encoding: self,
+            ..view
+        })
+    }
+
+    pub fn encode_history(self, page: HistoryPage) -> anyhow::Result<HistoryPage> {
+        let events = page
+            .events
+            .into_iter()
+            .map(|event| {
+                Ok(AccountEvent {
+                    tx_id: self.reencode_option(event.tx_id)?,
+                    signing_key: self.reencode_option(event.signing_key)?,
+                    key: self.reencode_option(event.key)?,
+                    ..event
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(HistoryPage { events, encoding: self, ..page })
+    }
+
+    pub fn encode_session(self, session: SessionKey) -> anyhow::Result<SessionKey> {
+        Ok(SessionKey {
+            verifying_key: self.reencode(&session.verifying_key)?,
+            authorized_by: self.reencode(&session.authorized_by)?,
+            ..session
+        })
+    }
+
+    pub fn encode_sessions(self, sessions: Vec<SessionKey>) -> anyhow::Result<Vec<SessionKey>> {
+        sessions.into_iter().map(|session| self.encode_session(session)).collect()
+    }
+
+    pub fn encode_recovery(self, recovery: RecoveryRequest) -> anyhow::Result<RecoveryRequest> {
+        let approvals = recovery
+            .approvals
+            .into_iter()
+            .map(|approval| {
+                Ok(GuardianApproval {
+                    signing_key: self.reencode(&approval.signing_key)?,
+                    ..approval
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(RecoveryRequest {
+            new_key: self.reencode(&recovery.new_key)?,
+            approvals,
+            tx_id: self.reencode_option(recovery.tx_id)?,
+            ..recovery
+        })
+    }
+
+    pub fn encode_service_key_grant(
+        self,
+        grant: ServiceKeyGrant,
+    ) -> anyhow::Result<ServiceKeyGrant> {
+        Ok(ServiceKeyGrant {
+            service_key: self.reencode(&grant.service_key)?,
+            tx_id: self.reencode(&grant.tx_id)?,
+        })
+    }
+
+    pub fn encode_account_recovery(
+        self,
+        recovery: AccountRecovery,
+    ) -> anyhow::Result<AccountRecovery> {
+        let recoveries = recovery.recoveries.into_iter().map(|r| self.encode_recovery(r));
+        Ok(AccountRecovery { recoveries: recoveries.collect::<anyhow::Result<_>>()?, ..recovery })
+    }
+
+    pub fn encode_revocation(
+        self,
+        revocation: PendingRevocation,
+    ) -> anyhow::Result<PendingRevocation> {
+        Ok(PendingRevocation {
+            key: self.reencode(&revocation.key)?,
+            tx_id: self.reencode_option(revocation.tx_id)?,
+            ..revocation
+        })
+    }
+
+    pub fn encode_report(self, report: DriftReport) -> anyhow::Result<DriftReport> {
+        let reencode = |keys: Vec<String>| -> anyhow::Result<Vec<String>> {
+            keys.iter().map(|key| self.reencode(key)).collect()
+        };
+        let accounts = report
+            .accounts
+            .into_iter()
+            .map(|account| {
+                Ok(AccountDrift {
+                    pending_keys: reencode(account.pending_keys)?,
+                    offchain_keys: reencode(account.offchain_keys)?,
+                    ..account
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(DriftReport { accounts, encoding: self, ..report })
+    }
+
+    fn reencode_option(self, value: Option<String>) -> anyhow::Result<Option<String>> {
+        value.map(|value| self.reencode(&value)).transpose()
+    }
+}
+
+#[derive(Deserialize)]
+struct EncodingQuery {
+    encoding: Option<String>,
+}
+
+// Encoding requested with the `encoding` query parameter, or the `encoding`
+// parameter of the Accept header, e.g. `application/json; encoding=hex`
+impl<S: Send + Sync> FromRequestParts<S> for BinaryEncoding {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = Query::<EncodingQuery>::try_from_uri(&parts.uri).ok();
+        let accept = parts.headers.get(ACCEPT).and_then(|value| value.to_str().ok());
+        let requested = query.and_then(|query| query.0.encoding).or_else(|| {
+            let params = accept?.split([',', ';']);
+            params.filter_map(|param| param.split_once('=')).find_map(|(name, value)| {
+                let value = value.trim().trim_matches('"').to_string();
+                name.trim().eq_ignore_ascii_case("encoding").then_some(value)
+            })
+        });
+        match requested {
+            Some(value) => BinaryEncoding::parse(&value).map_err(|e| AppError(e.into())),
+            None => Ok(BinaryEncoding::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reencode() {
+        let base64 = vec![0xde, 0xad, 0xbe, 0xef].to_base64();
+        assert_eq!(BinaryEncoding::Base64.reencode(&base64).unwrap(), base64);
+        assert_eq!(BinaryEncoding::Hex.reencode(&base64).unwrap(), "deadbeef");
+        // Values that are not base64 are not passed on as they are
+        assert!(BinaryEncoding::Hex.reencode("not base64!").is_err());
+        assert_eq!(BinaryEncoding::Hex.decode("deadbeef").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(BinaryEncoding::parse("HEX").unwrap(), BinaryEncoding::Hex);
+        assert!(BinaryEncoding::parse("base58").is_err());
+    }
+}
@@ -0,0 +1,364 @@
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use axum::http::{Method, StatusCode};
+use prost::Message as _;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::{Stream, StreamExt as _};
+use tonic::metadata::MetadataValue;
+use tonic::transport::Server;
+use tonic::{Code, Request, Response, Status};
+
+use crate::app::{AppState, StatusError};
+use crate::config::AppConfig;
+use crate::data::NewData;
+use crate::history::AccountEvent;
+use crate::idempotency::{Claim, StoredResponse, fingerprint};
+use crate::key_metadata::KeyMetadata;
+use crate::ops::{
+    AccountView, Source, SourcedData, SourcedValue, add_data, add_key, build_account_view,
+    list_account_views, lookup_account_id, request_create_account, send_create_account,
+};
+use crate::rate_limit::{check_route, retry_after_secs};
+use crate::utils::{parse_cosmos_adr36_verifying_key, parse_signature_bundle};
+
+use self::proto::prism_be_server::{PrismBe, PrismBeServer};
+
+// Types and service traits generated from proto/prism_be.proto
+pub mod proto {
+    tonic::include_proto!("prism_be.v1");
+}
+
+// gRPC service exposing the same operations as the HTTP API on top of `ops`
+pub struct GrpcService {
+    app: Arc<AppState>,
+}
+
+// Metadata entry carrying the idempotency key of a call, like the
+// `Idempotency-Key` header of HTTP requests
+const IDEMPOTENCY_KEY_METADATA: &str = "idempotency-key";
+
+impl GrpcService {
+    pub fn new(app: Arc<AppState>) -> Self {
+        Self { app }
+    }
+
+    // Take a token from the rate limit buckets of the HTTP route matching a
+    // call, keyed by the peer address and by the account id of the call
+    fn check_rate_limit(
+        &self,
+        route: &str,
+        ip: Option<IpAddr>,
+        account_id: Option<&str>,
+    ) -> Result<(), Status> {
+        check_route(&self.app, route, ip, account_id).map_err(|wait| {
+            let mut status = Status::resource_exhausted("Rate limit exceeded");
+            status.metadata_mut().insert("retry-after", retry_after_secs(wait).into());
+            status
+        })
+    }
+
+    // Run a mutating call. A call sent again with the same idempotency key
+    // and message gets the response of the first one back, like over HTTP.
+    // Failed calls are not stored, so they can be retried.
+    async fn idempotent<M, R, F>(
+        &self,
+        method: &str,
+        key: Option<String>,
+        message: M,
+        call: impl FnOnce(M) -> F,
+    ) -> Result<Response<R>, Status>
+    where
+        M: prost::Message,
+        R: prost::Message + Default,
+        F: Future<Output = Result<R, Status>>,
+    {
+        let Some(key) = key else {
+            return call(message).await.map(Response::new);
+        };
+        let path = format!("/prism_be.v1.PrismBe/{}", method);
+        let key = format!("{}:{}", path, key);
+        let fingerprint = fingerprint(&Method::POST, &path, &message.encode_to_vec());
+        let window = Duration::from_secs(self.app.config.idempotency.window_secs);
+
+        match self.app.idempotency.claim(&key, fingerprint, window, Instant::now()) {
+            Claim::New => {}
+            Claim::Replay(stored) => {
+                let reply = R::decode(stored.body)
+                    .map_err(|e| Status::internal(format!("Invalid stored response: {}", e)))?;
+                let mut response = Response::new(reply);
+                let replayed = MetadataValue::from_static("true");
+                response.metadata_mut().insert("idempotent-replayed", replayed);
+                return Ok(response);
+            }
+            Claim::InProgress => {
+                let message = "A call with this idempotency key is in progress";
+                return Err(Status::already_exists(message));
+            }
+            Claim::Mismatch => {
+                let message = "Idempotency key was used for a different call";
+                return Err(Status::invalid_argument(message));
+            }
+        }
+
+        match call(message).await {
+            Ok(reply) => {
+                let body = reply.encode_to_vec().into();
+                let stored = StoredResponse { status: StatusCode::OK, content_type: None, body };
+                self.app.idempotency.complete(&key, stored);
+                Ok(Response::new(reply))
+            }
+            Err(e) => {
+                self.app.idempotency.release(&key);
+                Err(e)
+            }
+        }
+    }
+}
+
+// Client IP of a call, for the per-IP rate limits
+fn peer_ip<T>(request: &Request<T>) -> Option<IpAddr> {
+    request.remote_addr().map(|addr| addr.ip())
+}
+
+fn idempotency_key<T>(request: &Request<T>) -> Option<String> {
+    let key = request.metadata().get(IDEMPOTENCY_KEY_METADATA)?;
+    key.to_str().ok().map(str::to_string)
+}
+
+// Service to be served by `run_grpc_server` or added to another tonic server.
+// Messages are limited to the maximum body size of the HTTP API.
+pub fn grpc_service(app_state: Arc<AppState>) -> PrismBeServer<GrpcService> {
+    let max_message_bytes = app_state.config.limits.max_body_bytes;
+    PrismBeServer::new(GrpcService::new(app_state)).max_decoding_message_size(max_message_bytes)
+}
+
+// Serve the gRPC service on the configured port until Ctrl-C is received
+pub async fn run_grpc_server(app_state: Arc<AppState>, config: AppConfig) -> anyhow::Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.grpc.port));
+    tracing::info!("Serving gRPC on {}", addr);
+    Server::builder()
+        .add_service(grpc_service(app_state))
+        .serve_with_shutdown(addr, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .with_context(|| format!("Failed to serve gRPC on {}", addr))
+}
+
+// Report an ops error with the gRPC code matching its HTTP status
+fn status(e: anyhow::Error) -> Status {
+    let code = match e.downcast_ref::<StatusError>().map(|e| e.status) {
+        Some(StatusCode::BAD_REQUEST) => Code::InvalidArgument,
+        Some(StatusCode::FORBIDDEN) => Code::PermissionDenied,
+        Some(StatusCode::NOT_FOUND) => Code::NotFound,
+        Some(StatusCode::CONFLICT) => Code::AlreadyExists,
+        Some(StatusCode::PAYLOAD_TOO_LARGE | StatusCode::TOO_MANY_REQUESTS) => {
+            Code::ResourceExhausted
+        }
+        _ => Code::Internal,
+    };
+    Status::new(code, format!("{:#}", e))
+}
+
+fn invalid_argument(message: &str) -> impl FnOnce(anyhow::Error) -> Status + '_ {
+    move |e| Status::invalid_argument(format!("{}: {:#}", message, e))
+}
+
+impl From<Source> for proto::Source {
+    fn from(source: Source) -> Self {
+        match source {
+            Source::OnChain => proto::Source::OnChain,
+            Source::OffChainPending => proto::Source::OffChainPending,
+            Source::OffChainOnly => proto::Source::OffChainOnly,
+        }
+    }
+}
+
+impl From<KeyMetadata> for proto::KeyMetadata {
+    fn from(metadata: KeyMetadata) -> Self {
+        Self {
+            label: metadata.label,
+            wallet_type: metadata.wallet_type,
+            created_at: metadata.created_at,
+            last_used_at: metadata.last_used_at,
+            added_by: metadata.added_by,
+            expires_at: metadata.expires_at,
+        }
+    }
+}
+
+impl From<SourcedValue> for proto::SourcedValue {
+    fn from(key: SourcedValue) -> Self {
+        Self {
+            value: key.value,
+            source: proto::Source::from(key.source).into(),
+            metadata: Some(key.metadata.into()),
+        }
+    }
+}
+
+impl From<SourcedData> for proto::DataEntry {
+    fn from(data: SourcedData) -> Self {
+        let record = data.record;
+        Self {
+            id: record.id,
+            revision: record.revision,
+            r#type: record.type_tag,
+            content_type: record.content_type,
+            created_at: record.created_at,
+            schema: record.schema,
+            value: record.value,
+            source: proto::Source::from(data.source).into(),
+        }
+    }
+}
+
+impl From<AccountView> for proto::AccountView {
+    fn from(view: AccountView) -> Self {
+        Self {
+            id: view.id,
+            nonce: view.nonce,
+            keys: view.keys.into_iter().map(Into::into).collect(),
+            data: view.data.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+fn event_message(account_id: String, event: AccountEvent) -> proto::AccountEvent {
+    let kind = serde_json::to_value(event.kind).ok();
+    proto::AccountEvent {
+        account_id,
+        seq: event.seq,
+        kind: kind.as_ref().and_then(|kind| kind.as_str()).unwrap_or_default().to_string(),
+        timestamp: event.timestamp,
+        tx_id: event.tx_id,
+        epoch: event.epoch,
+        signing_key: event.signing_key,
+        key: event.key,
+        data_id: event.data_id,
+    }
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<proto::AccountEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl PrismBe for GrpcService {
+    type StreamEventsStream = EventStream;
+
+    async fn request_create_account(
+        &self,
+        request: Request<proto::RequestCreateAccountRequest>,
+    ) -> Result<Response<proto::PayloadResponse>, Status> {
+        let (ip, key) = (peer_ip(&request), idempotency_key(&request));
+        let req = request.into_inner();
+        self.idempotent("RequestCreateAccount", key, req, |req| async move {
+            self.check_rate_limit("/v1/account/request-create", ip, Some(&req.id))?;
+            let verifying_key = parse_cosmos_adr36_verifying_key(req.verifying_key)
+                .map_err(invalid_argument("Invalid verifying key"))?;
+            let payload = request_create_account(self.app.clone(), req.id, verifying_key)
+                .await
+                .map_err(status)?;
+            Ok(proto::PayloadResponse { payload })
+        })
+        .await
+    }
+
+    async fn send_create_account(
+        &self,
+        request: Request<proto::SendCreateAccountRequest>,
+    ) -> Result<Response<proto::AccountResult>, Status> {
+        let (ip, key) = (peer_ip(&request), idempotency_key(&request));
+        let req = request.into_inner();
+        self.idempotent("SendCreateAccount", key, req, |req| async move {
+            self.check_rate_limit("/v1/account/send-create", ip, Some(&req.id))?;
+            let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+                .map_err(invalid_argument("Invalid signature bundle"))?;
+            let account = send_create_account(self.app.clone(), req.id, signature_bundle)
+                .await
+                .map_err(status)?;
+            Ok(proto::AccountResult { id: account.id().to_string() })
+        })
+        .await
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<proto::AccountQuery>,
+    ) -> Result<Response<proto::AccountView>, Status> {
+        let ip = peer_ip(&request);
+        let id = request.into_inner().id;
+        self.check_rate_limit("/v1/account/get", ip, Some(&id))?;
+        let view = build_account_view(self.app.clone(), id).await.map_err(status)?;
+        Ok(Response::new(view.into()))
+    }
+
+    async fn add_key(
+        &self,
+        request: Request<proto::AddKeyRequest>,
+    ) -> Result<Response<proto::AccountResult>, Status> {
+        let (ip, key) = (peer_ip(&request), idempotency_key(&request));
+        let req = request.into_inner();
+        self.idempotent("AddKey", key, req, |req| async move {
+            self.check_rate_limit("/v1/account/add-key", ip, Some(&req.id))?;
+            let new_key = parse_cosmos_adr36_verifying_key(req.verifying_key)
+                .map_err(invalid_argument("Invalid verifying key"))?;
+            let account =
+                add_key(self.app.clone(), req.id, new_key, req.expires_at).await.map_err(status)?;
+            Ok(proto::AccountResult { id: account.id().to_string() })
+        })
+        .await
+    }
+
+    async fn add_data(
+        &self,
+        request: Request<proto::AddDataRequest>,
+    ) -> Result<Response<proto::AccountResult>, Status> {
+        let (ip, key) = (peer_ip(&request), idempotency_key(&request));
+        let req = request.into_inner();
+        self.idempotent("AddData", key, req, |req| async move {
+            self.check_rate_limit("/v1/account/add-data", ip, Some(&req.id))?;
+            let data = NewData::with_defaults(req.data, req.r#type, req.content_type, req.schema);
+            let account = add_data(self.app.clone(), req.id, data).await.map_err(status)?;
+            Ok(proto::AccountResult { id: account.id().to_string() })
+        })
+        .await
+    }
+
+    async fn list_accounts(
+        &self,
+        request: Request<proto::ListAccountsRequest>,
+    ) -> Result<Response<proto::ListAccountsResponse>, Status> {
+        self.check_rate_limit("/v1/account/list-accounts", peer_ip(&request), None)?;
+        let views = list_account_views(self.app.clone()).await;
+        let accounts = views.into_iter().map(Into::into).collect();
+        Ok(Response::new(proto::ListAccountsResponse { accounts }))
+    }
+
+    async fn stream_events(
+        &self,
+        request: Request<proto::StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let filter = request.into_inner().id.map(|id| lookup_account_id(&self.app, &id));
+        let events = BroadcastStream::new(self.app.db.subscribe_events()).filter_map(move |item| {
+            match item {
+                Ok((id, event)) => filter
+                    .as_ref()
+                    .is_none_or(|filter| *filter == id)
+                    .then(|| Ok(event_message(id, event))),
+                // Events missed by a slow subscriber can be read back from
+                // the account history
+                Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                    tracing::warn!("Event stream subscriber missed {} events", missed);
+                    None
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+}
@@ -0,0 +1,99 @@
+use prism_client::SignatureBundle;
+use prism_serde::base64::ToBase64;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::utils::now_secs;
+
+// Default and maximum number of events returned in one history page
+pub const DEFAULT_HISTORY_PAGE_SIZE: usize = 50;
+pub const MAX_HISTORY_PAGE_SIZE: usize = 500;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Created,
+    KeyAdded,
+    KeyRevoked,
+    KeyExpired,
+    DataAdded,
+    DataUpdated,
+    DataDeleted,
+    SessionCreated,
+    SessionRevoked,
+    RecoveryConfigured,
+    RecoveryStarted,
+    RecoveryApproved,
+    RecoveryCancelled,
+    RecoveryExecuted,
+}
+
+// Entry of the append-only event log of an account
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountEvent {
+    // Position of the event in the account's log, starting at 0
+    pub seq: u64,
+    pub kind: EventKind,
+    // Unix timestamp in seconds
+    pub timestamp: u64,
+    // Service-local id of the transaction submitted to the prover, if any. See
+    // `transaction_id`.
+    pub tx_id: Option<String>,
+    // Prover epoch (DA height) the transaction was included in, once observed
+    // on-chain
+    pub epoch: Option<u64>,
+    // Key that authorized the change, if it was signed
+    pub signing_key: Option<String>,
+    // Key added or revoked by the event
+    pub key: Option<String>,
+    // Id of the data entry added or changed by the event
+    pub data_id: Option<String>,
+}
+
+impl AccountEvent {
+    // Event of the given kind happening now. The sequence number is assigned
+    // when the event is appended to the log.
+    pub fn new(kind: EventKind) -> Self {
+        Self {
+            seq: 0,
+            kind,
+            timestamp: now_secs(),
+            tx_id: None,
+            epoch: None,
+            signing_key: None,
+            key: None,
+            data_id: None,
+        }
+    }
+
+    pub fn with_tx_id(mut self, tx_id: String) -> Self {
+        self.tx_id = Some(tx_id);
+        self
+    }
+
+    pub fn with_signing_key(mut self, signing_key: String) -> Self {
+        self.signing_key = Some(signing_key);
+        self
+    }
+
+    pub fn with_key(mut self, key: String) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn with_data_id(mut self, data_id: String) -> Self {
+        self.data_id = Some(data_id);
+        self
+    }
+}
+
+// Id the service gives a signed transaction, the base64 SHA-256 hash of its
+// signing payload and signature. It is not the hash Prism uses for the
+// transaction and only correlates history events, revocations and logs of
+// this service.
+pub fn transaction_id(signing_payload: &[u8], signature_bundle: &SignatureBundle) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(signing_payload);
+    hasher.update(signature_bundle.signature.to_bytes());
+    hasher.finalize().to_base64()
+}
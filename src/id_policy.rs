@@ -0,0 +1,99 @@
+use crate::app::StatusError;
+use crate::config::{AccountIdConfig, AccountIdFormat};
+
+// Normalize an account id and check it against the configured policy.
+// Returns the id the account should be stored under.
+pub fn normalize_account_id(config: &AccountIdConfig, id: &str) -> Result<String, StatusError> {
+    let id = normalize_case(config, id);
+
+    let length = id.chars().count();
+    if length < config.min_length || length > config.max_length {
+        return Err(StatusError::bad_request(format!(
+            "Account id must be between {} and {} characters long",
+            config.min_length, config.max_length
+        )));
+    }
+
+    if let Some(c) =
+        id.chars().find(|c| !c.is_ascii_alphanumeric() && !config.allowed_symbols.contains(*c))
+    {
+        let message = format!("Account id contains invalid character {c:?}");
+        return Err(StatusError::bad_request(message));
+    }
+
+    match config.format {
+        Some(AccountIdFormat::Email) if !is_email(&id) => {
+            Err(StatusError::bad_request("Account id must be an email address"))
+        }
+        Some(AccountIdFormat::Domain) if !is_domain(&id) => {
+            Err(StatusError::bad_request("Account id must be a domain name"))
+        }
+        _ => Ok(id),
+    }
+}
+
+// Apply the configured case of account ids, without checking the policy
+pub fn normalize_case(config: &AccountIdConfig, id: &str) -> String {
+    if config.lowercase { id.to_lowercase() } else { id.to_string() }
+}
+
+// Whether the id matches one of the reserved names, ignoring case
+pub fn is_reserved<'a>(reserved: impl IntoIterator<Item = &'a String>, id: &str) -> bool {
+    reserved.into_iter().any(|name| name.eq_ignore_ascii_case(id))
+}
+
+fn is_email(id: &str) -> bool {
+    match id.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && !local.contains('@') && is_domain(domain),
+        None => false,
+    }
+}
+
+fn is_domain(id: &str) -> bool {
+    let labels: Vec<&str> = id.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_account_id() {
+        let mut config = AccountIdConfig::default();
+        assert_eq!(normalize_account_id(&config, "alice_01").unwrap(), "alice_01");
+        assert!(normalize_account_id(&config, "").is_err());
+        assert!(normalize_account_id(&config, &"a".repeat(config.max_length + 1)).is_err());
+        assert!(normalize_account_id(&config, "alice bob").is_err());
+
+        config.lowercase = true;
+        assert_eq!(normalize_account_id(&config, "Alice").unwrap(), "alice");
+    }
+
+    #[test]
+    fn test_account_id_formats() {
+        let mut config =
+            AccountIdConfig { format: Some(AccountIdFormat::Email), ..Default::default() };
+        assert!(normalize_account_id(&config, "alice@example.com").is_ok());
+        assert!(normalize_account_id(&config, "alice@example").is_err());
+        assert!(normalize_account_id(&config, "alice").is_err());
+
+        config.format = Some(AccountIdFormat::Domain);
+        assert!(normalize_account_id(&config, "alice.example.com").is_ok());
+        assert!(normalize_account_id(&config, "-alice.com").is_err());
+    }
+
+    #[test]
+    fn test_is_reserved() {
+        let reserved = vec!["admin".to_string()];
+        assert!(is_reserved(&reserved, "Admin"));
+        assert!(!is_reserved(&reserved, "alice"));
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, Bytes, to_bytes};
+use axum::extract::{Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+
+use crate::app::AppState;
+
+pub const IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+pub const IDEMPOTENT_REPLAYED_HEADER: HeaderName =
+    HeaderName::from_static("idempotent-replayed");
+
+// Response stored for an idempotency key
+#[derive(Clone)]
+pub struct StoredResponse {
+    pub status: StatusCode,
+    pub content_type: Option<HeaderValue>,
+    pub body: Bytes,
+}
+
+#[derive(Clone)]
+enum EntryState {
+    InProgress,
+    Completed(StoredResponse),
+}
+
+struct Entry {
+    // Hash of the method, path and body of the request that used the key
+    fingerprint: [u8; 32],
+    state: EntryState,
+    created_at: Instant,
+}
+
+// Outcome of claiming an idempotency key for a request
+pub enum Claim {
+    // First request with this key, it should be processed
+    New,
+    // A request with this key completed within the window
+    Replay(StoredResponse),
+    // A request with this key is still being processed
+    InProgress,
+    // The key was used for a different request
+    Mismatch,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    // Keys in the order they were claimed, with their claim time. Released
+    // keys stay here until they reach the front.
+    order: VecDeque<(String, Instant)>,
+}
+
+impl Entries {
+    // Drop the oldest claim, unless its key was claimed again since
+    fn pop_oldest(&mut self) {
+        let Some((key, created_at)) = self.order.pop_front() else {
+            return;
+        };
+        if self.by_key.get(&key).is_some_and(|entry| entry.created_at == created_at) {
+            self.by_key.remove(&key);
+        }
+    }
+}
+
+// Responses of mutating requests keyed by their idempotency key, holding at
+// most `max_entries` keys
+pub struct IdempotencyStore {
+    entries: Mutex<Entries>,
+    max_entries: usize,
+}
+
+impl IdempotencyStore {
+    pub fn new(max_entries: usize) -> Self {
+        Self { entries: Mutex::new(Entries::default()), max_entries: max_entries.max(1) }
+    }
+
+    // Claim a key for a request with the given fingerprint. Keys older than
+    // the window are dropped first, then the oldest keys if the store is full.
+    pub fn claim(
+        &self,
+        key: &str,
+        fingerprint: [u8; 32],
+        window: Duration,
+        now: Instant,
+    ) -> Claim {
+        let mut entries = self.entries.lock().unwrap();
+        while let Some((_, created_at)) = entries.order.front() {
+            if now.saturating_duration_since(*created_at) < window {
+                break;
+            }
+            entries.pop_oldest();
+        }
+        match entries.by_key.get(key) {
+            Some(entry) if entry.fingerprint != fingerprint => Claim::Mismatch,
+            Some(entry) => match &entry.state {
+                EntryState::InProgress => Claim::InProgress,
+                EntryState::Completed(response) => Claim::Replay(response.clone()),
+            },
+            None => {
+                while entries.order.len() >= self.max_entries {
+                    entries.pop_oldest();
+                }
+                let entry = Entry { fingerprint, state: EntryState::InProgress, created_at: now };
+                entries.by_key.insert(key.to_string(), entry);
+                entries.order.push_back((key.to_string(), now));
+                Claim::New
+            }
+        }
+    }
+
+    // Store the response of a claimed key so it is replayed for duplicates
+    pub fn complete(&self, key: &str, response: StoredResponse) {
+        if let Some(entry) = self.entries.lock().unwrap().by_key.get_mut(key) {
+            entry.state = EntryState::Completed(response);
+        }
+    }
+
+    // Release a claimed key so the request can be retried
+    pub fn release(&self, key: &str) {
+        self.entries.lock().unwrap().by_key.remove(key);
+    }
+}
+
+// Hash identifying a request, so a key reused for another request is detected
+pub fn fingerprint(method: &Method, path: &str, body: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update([0]);
+    hasher.update(path);
+    hasher.update([0]);
+    hasher.update(body);
+    hasher.finalize().into()
+}
+
+// Middleware replaying the stored response of a mutating request sent again
+// with the same `Idempotency-Key` header. Keys are scoped to the path of the
+// request, not to the client address, so retries from another network are
+// still recognized; a key reused for another body is rejected by the request
+// fingerprint. Server errors and rate limited responses are not stored, so
+// those requests can be retried.
+pub async fn idempotency(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let mutating =
+        matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH | Method::DELETE);
+    let key = req.headers().get(IDEMPOTENCY_KEY_HEADER).and_then(|key| key.to_str().ok());
+    let Some(key) = key.filter(|_| mutating) else {
+        return next.run(req).await;
+    };
+    let key = format!("{}:{}", req.uri().path(), key);
+
+    let (parts, body) = req.into_parts();
+    let Ok(body) = to_bytes(body, state.config.limits.max_body_bytes).await else {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "Error: Request body too large").into_response();
+    };
+    let fingerprint = fingerprint(&parts.method, parts.uri.path(), &body);
+    let window = Duration::from_secs(state.config.idempotency.window_secs);
+
+    match state.idempotency.claim(&key, fingerprint, window, Instant::now()) {
+        Claim::New => {}
+        Claim::Replay(stored) => return replay(stored),
+        Claim::InProgress => {
+            let message = "Error: A request with this idempotency key is in progress";
+            return (StatusCode::CONFLICT, message).into_response();
+        }
+        Claim::Mismatch => {
+            let message = "Error: Idempotency key was used for a different request";
+            return (StatusCode::UNPROCESSABLE_ENTITY, message).into_response();
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        state.idempotency.release(&key);
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, usize::MAX).await else {
+        state.idempotency.release(&key);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let content_type = parts.headers.get(CONTENT_TYPE).cloned();
+    state.idempotency.complete(&key, StoredResponse { status, content_type, body: body.clone() });
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = (stored.status, stored.body).into_response();
+    if let Some(content_type) = stored.content_type {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    response.headers_mut().insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_store_claims() {
+        let store = IdempotencyStore::new(10);
+        let window = Duration::from_secs(60);
+        let start = Instant::now();
+        let request = fingerprint(&Method::POST, "/v1/account/add-key", b"{}");
+        let other = fingerprint(&Method::POST, "/v1/account/add-key", b"{\"id\":1}");
+
+        assert!(matches!(store.claim("key", request, window, start), Claim::New));
+        assert!(matches!(store.claim("key", request, window, start), Claim::InProgress));
+        assert!(matches!(store.claim("key", other, window, start), Claim::Mismatch));
+
+        let stored = StoredResponse {
+            status: StatusCode::OK,
+            content_type: None,
+            body: Bytes::from_static(b"ok"),
+        };
+        store.complete("key", stored);
+        match store.claim("key", request, window, start) {
+            Claim::Replay(response) => assert_eq!(response.body, Bytes::from_static(b"ok")),
+            _ => panic!("expected the stored response to be replayed"),
+        }
+
+        // Keys expire after the window
+        let later = start + window;
+        assert!(matches!(store.claim("key", request, window, later), Claim::New));
+    }
+
+    #[test]
+    fn test_idempotency_store_evicts_oldest_keys() {
+        let store = IdempotencyStore::new(2);
+        let window = Duration::from_secs(60);
+        let now = Instant::now();
+        let request = fingerprint(&Method::POST, "/v1/account/add-key", b"{}");
+
+        assert!(matches!(store.claim("a", request, window, now), Claim::New));
+        assert!(matches!(store.claim("b", request, window, now), Claim::New));
+        assert!(matches!(store.claim("c", request, window, now), Claim::New));
+        assert!(matches!(store.claim("b", request, window, now), Claim::InProgress));
+        assert!(matches!(store.claim("a", request, window, now), Claim::New));
+
+        // Released keys do not hold a place in the store
+        store.release("c");
+        store.release("a");
+        for _ in 0..10 {
+            assert!(matches!(store.claim("d", request, window, now), Claim::New));
+            store.release("d");
+        }
+        assert_eq!(store.entries.lock().unwrap().order.len(), 2);
+    }
+}
@@ -0,0 +1,110 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use prism_client::AccountResponse;
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppState;
+use crate::history::{AccountEvent, EventKind};
+use crate::ops::fetch_prover_account;
+use crate::utils::now_secs;
+
+// Expired key that is on-chain or pending inclusion. Only a transaction signed
+// by the account can revoke it, so it waits for a user signature.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PendingRevocation {
+    pub key: String,
+    pub expired_at: u64,
+    // Service-local id of the revoke transaction once it has been submitted
+    pub tx_id: Option<String>,
+}
+
+// Key handled by a run of the scheduler
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExpiredKey {
+    pub account_id: String,
+    pub key: String,
+    // Whether the service revoked the key itself, otherwise the revocation
+    // is queued for a user signature
+    pub revoked: bool,
+}
+
+// Run the key expiry scheduler forever at the configured interval
+pub async fn run_key_expiry(app: Arc<AppState>) {
+    let interval = Duration::from_secs(app.config.key_expiry.interval_secs.max(1));
+    loop {
+        let expired = expire_keys(app.clone()).await;
+        if !expired.is_empty() {
+            tracing::info!("Handled {} expired keys", expired.len());
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+// Revoke the keys that expired since the last run. Off-chain keys are revoked
+// by the service right away. Keys on-chain or pending inclusion are queued for
+// a revoke transaction signed by the account, and dropped from the queue once
+// they are no longer on-chain. Views leave expired keys out in the meantime.
+pub async fn expire_keys(app: Arc<AppState>) -> Vec<ExpiredKey> {
+    let fetch_account =
+        |app: Arc<AppState>, id: String| async move { fetch_prover_account(&app, &id).await };
+    expire_keys_with(app, fetch_account).await
+}
+
+// Run of `expire_keys` reading accounts through the given function. Accounts
+// that cannot be read are skipped until the next run, so one failing account
+// does not hold up the others.
+pub async fn expire_keys_with<F, Fut>(app: Arc<AppState>, fetch_account: F) -> Vec<ExpiredKey>
+where
+    F: Fn(Arc<AppState>, String) -> Fut,
+    Fut: Future<Output = anyhow::Result<AccountResponse>>,
+{
+    let now = now_secs();
+    let mut expired = Vec::new();
+    for (account_id, keys) in app.db.get_expired_keys(now) {
+        let account = match fetch_account(app.clone(), account_id.clone()).await {
+            Ok(response) => response.account.unwrap_or_default(),
+            Err(e) => {
+                tracing::error!("Key expiry failed for account {}: {:?}", &account_id, e);
+                continue;
+            }
+        };
+        let onchain_keys: Vec<String> =
+            account.valid_keys().iter().map(|key| key.to_string()).collect();
+        let pending_keys = app.db.get_pending_keys(account_id.clone());
+        let queued: Vec<String> = app
+            .db
+            .get_pending_revocations(account_id.clone())
+            .into_iter()
+            .map(|revocation| revocation.key)
+            .collect();
+
+        for (key, expired_at) in keys {
+            let revoked = if onchain_keys.contains(&key) || pending_keys.contains(&key) {
+                if queued.contains(&key) {
+                    continue;
+                }
+                let revocation = PendingRevocation { key: key.clone(), expired_at, tx_id: None };
+                app.db.queue_revocation(account_id.clone(), revocation);
+                tracing::info!("Queued revocation of an expired key of account {}", &account_id);
+                false
+            } else if app.db.remove_key(account_id.clone(), key.clone()) {
+                app.db.remove_key_metadata(account_id.clone(), &key);
+                tracing::info!("Revoked an expired off-chain key of account {}", &account_id);
+                true
+            } else {
+                // The key was revoked on-chain or otherwise removed
+                app.db.remove_key_metadata(account_id.clone(), &key);
+                app.db.remove_pending_revocation(account_id.clone(), &key);
+                continue;
+            };
+            app.db.append_event(
+                account_id.clone(),
+                AccountEvent::new(EventKind::KeyExpired).with_key(key.clone()),
+            );
+            expired.push(ExpiredKey { account_id: account_id.clone(), key, revoked });
+        }
+    }
+    expired
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+// Maximum length of the user managed metadata fields
+pub const MAX_METADATA_FIELD_LEN: usize = 128;
+
+// Off-chain information about a key of an account, so users can tell their
+// keys apart. Prism only stores the keys themselves.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyMetadata {
+    // Name given by the user, e.g. "Phone"
+    pub label: Option<String>,
+    // Wallet holding the key, e.g. "keplr"
+    pub wallet_type: Option<String>,
+    pub created_at: Option<u64>,
+    // Last time the key signed a request accepted by the service
+    pub last_used_at: Option<u64>,
+    // Key that signed the addition of this key, set by the service: the key
+    // itself for the key an account was created with, the service key for
+    // keys the service added
+    pub added_by: Option<String>,
+    // Unix timestamp in seconds from which the key is no longer valid
+    pub expires_at: Option<u64>,
+    // Incremented on every change of the user managed fields and part of the
+    // signed payload, so an old change cannot be replayed
+    #[serde(default)]
+    pub revision: u64,
+}
+
+impl KeyMetadata {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+// Change to the user managed fields of a key's metadata. Fields left out are
+// kept, and empty strings clear them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyMetadataUpdate {
+    pub label: Option<String>,
+    pub wallet_type: Option<String>,
+}
+
+impl KeyMetadataUpdate {
+    pub fn apply(self, metadata: &mut KeyMetadata) {
+        let fields =
+            [(self.label, &mut metadata.label), (self.wallet_type, &mut metadata.wallet_type)];
+        for (value, field) in fields {
+            if let Some(value) = value {
+                *field = Some(value).filter(|value| !value.is_empty());
+            }
+        }
+        metadata.revision += 1;
+    }
+}
+
+#[derive(Serialize)]
+struct KeyMetadataPayload<'a> {
+    service_id: &'a str,
+    account_id: &'a str,
+    key: &'a str,
+    update: &'a KeyMetadataUpdate,
+    revision: u64,
+}
+
+// Bytes an account key has to sign to change the metadata of a key at the
+// given revision
+pub fn key_metadata_payload(
+    service_id: &str,
+    account_id: &str,
+    key: &str,
+    update: &KeyMetadataUpdate,
+    revision: u64,
+) -> Vec<u8> {
+    let payload = KeyMetadataPayload { service_id, account_id, key, update, revision };
+    serde_json::to_vec(&payload).expect("key metadata payload is always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_update() {
+        let mut metadata = KeyMetadata {
+            label: Some("Phone".to_string()),
+            wallet_type: Some("keplr".to_string()),
+            created_at: Some(1),
+            ..Default::default()
+        };
+        let update = KeyMetadataUpdate {
+            label: Some("Laptop".to_string()),
+            wallet_type: Some(String::new()),
+        };
+        update.apply(&mut metadata);
+        assert_eq!(metadata.label.as_deref(), Some("Laptop"));
+        assert_eq!(metadata.wallet_type, None);
+        assert_eq!(metadata.created_at, Some(1));
+        assert_eq!(metadata.revision, 1);
+    }
+}
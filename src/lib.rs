@@ -1,6 +1,24 @@
+pub mod api;
 pub mod app;
+pub mod auth;
+pub mod batch;
 pub mod config;
+pub mod content;
+pub mod data;
 pub mod db;
+pub mod encoding;
+pub mod grpc;
+pub mod history;
+pub mod id_policy;
+pub mod idempotency;
+pub mod key_expiry;
+pub mod key_metadata;
+pub mod logging;
 pub mod ops;
+pub mod rate_limit;
+pub mod reconciler;
+pub mod recovery;
 pub mod server;
+pub mod session;
+pub mod telemetry;
 pub mod utils;
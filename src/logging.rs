@@ -0,0 +1,37 @@
+use anyhow::anyhow;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_subscriber::layer::SubscriberExt as _;
+use tracing_subscriber::util::SubscriberInitExt as _;
+use tracing_subscriber::{EnvFilter, fmt};
+
+use crate::config::{LogFormat, LoggingConfig, TelemetryConfig};
+use crate::telemetry::init_tracer_provider;
+
+// Install the global tracing subscriber. JSON output includes the fields of
+// the current span and its parents, such as the request id and account id.
+// When telemetry is enabled spans are also exported over OTLP, and the
+// returned provider should be shut down on exit to flush them.
+pub fn init_logging(
+    config: &LoggingConfig,
+    telemetry: &TelemetryConfig,
+) -> anyhow::Result<Option<SdkTracerProvider>> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.level))
+        .map_err(|e| anyhow!("Invalid log level {}: {}", config.level, e))?;
+    let provider = telemetry.enabled.then(|| init_tracer_provider(telemetry)).transpose()?;
+    let tracer = provider.as_ref().map(|provider| provider.tracer("prism-be"));
+
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)));
+    match config.format {
+        LogFormat::Text => registry.with(fmt::layer()).try_init(),
+        LogFormat::Json => {
+            let layer = fmt::layer().json().with_current_span(true).with_span_list(true);
+            registry.with(layer).try_init()
+        }
+    }
+    .map_err(|e| anyhow!("Failed to install the log subscriber: {}", e))?;
+    Ok(provider)
+}
@@ -1,21 +1,249 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use anyhow::anyhow;
+use axum::http::StatusCode;
 use prism_client::{
     Account, AccountResponse, PendingTransaction as _, PrismApi as _, SignatureBundle, VerifyingKey,
 };
+use prism_common::transaction::{Transaction, UnsignedTransaction};
+use prism_serde::base64::ToBase64;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{Instrument as _, info_span};
+use uuid::Uuid;
 
-use crate::app::AppState;
+use crate::app::{AppState, StatusError};
+use crate::config::LimitsConfig;
+use crate::data::{
+    DataChange, DataRecord, NewData, ONCHAIN_ID_PREFIX, RAW_DATA_TYPE, check_schema,
+    data_change_payload, validate_value,
+};
+use crate::encoding::BinaryEncoding;
+use crate::history::{
+    AccountEvent, DEFAULT_HISTORY_PAGE_SIZE, EventKind, MAX_HISTORY_PAGE_SIZE, transaction_id,
+};
+use crate::id_policy::{is_reserved, normalize_account_id, normalize_case};
+use crate::key_expiry::PendingRevocation;
+use crate::key_metadata::{
+    KeyMetadata, KeyMetadataUpdate, MAX_METADATA_FIELD_LEN, key_metadata_payload,
+};
+use crate::reconciler::refresh_account;
+use crate::recovery::{
+    AccountRecovery, GuardianApproval, RecoveryRequest, RecoverySetup, RecoveryStatus,
+    ServiceKeyGrant, recovery_approval_payload, recovery_cancel_payload, recovery_setup_payload,
+    recovery_start_payload,
+};
+use crate::session::{
+    SessionKey, SessionOperation, SessionPermission, session_data_payload, session_grant_payload,
+};
+use crate::utils::{now_secs, parse_cosmos_adr36_verifying_key, remove_duplicates};
 
-pub async fn get_account(app: Arc<AppState>, user_id: String) -> anyhow::Result<AccountResponse> {
-    let account = app.prover.clone().get_account(&user_id).await?;
-    Ok(account)
+// Where a key or data entry of an account view comes from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    // Part of the account state committed by the prover
+    OnChain,
+    // Submitted to the prover but not yet included on-chain
+    OffChainPending,
+    // Only stored in the off-chain database
+    OffChainOnly,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourcedValue {
+    pub value: String,
+    pub source: Source,
+    #[serde(default)]
+    pub metadata: KeyMetadata,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourcedData {
+    #[serde(flatten)]
+    pub record: DataRecord,
+    pub source: Source,
+}
+
+// Merged view of an account across the prover and the off-chain database
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountView {
+    pub id: String,
+    pub nonce: u64,
+    pub keys: Vec<SourcedValue>,
+    pub data: Vec<SourcedData>,
+    // Encoding of the keys and raw data of the view
+    #[serde(default)]
+    pub encoding: BinaryEncoding,
+}
+
+// Fetch an account from the prover
+pub async fn fetch_prover_account(
+    app: &AppState,
+    user_id: &str,
+) -> anyhow::Result<AccountResponse> {
+    let span = info_span!("prover.get_account", account_id = %user_id);
+    Ok(app.prover.clone().get_account(user_id).instrument(span).await?)
+}
+
+// Refresh the key index of an account from its on-chain and pending keys
+pub fn index_account_keys(app: Arc<AppState>, user_id: String, account: &Account) {
+    let onchain_keys = account.valid_keys().iter().map(|key| key.to_string());
+    let pending_keys = app.db.get_pending_keys(user_id.clone());
+    app.db.reindex_keys(user_id, onchain_keys.chain(pending_keys).collect());
+}
+
+// Build the merged view of an account from the prover and the off-chain
+// database
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn build_account_view(
+    app: Arc<AppState>,
+    user_id: String,
+) -> anyhow::Result<AccountView> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let account = fetch_prover_account(&app, &user_id).await?.account.unwrap_or_default();
+    Ok(account_view(&app, user_id, &account))
+}
+
+// Merged view of an account with the given on-chain state. Entries are ordered
+// on-chain first, then pending, then off-chain only, each group in insertion
+// order, and a value appearing in several groups is only reported with its
+// first source. The off-chain database is only read: pending keys that made it
+// on-chain are cleared by the reconciler.
+pub fn account_view(app: &AppState, user_id: String, account: &Account) -> AccountView {
+    let onchain_keys: Vec<String> =
+        account.valid_keys().iter().map(|key| key.to_string()).collect();
+    let onchain_data: Vec<String> =
+        account.signed_data().iter().map(|data| data.data.to_base64()).collect();
+
+    // Expired keys are left out right away, before the scheduler revokes them
+    let now = now_secs();
+    let mut key_metadata = app.db.get_key_metadata(user_id.clone());
+    let keys = merge_sources(
+        onchain_keys,
+        app.db.get_pending_keys(user_id.clone()),
+        app.db.get_keys(user_id.clone()),
+        |key| key.as_str(),
+    )
+    .into_iter()
+    .map(|(value, source)| {
+        let metadata = key_metadata.remove(&value).unwrap_or_default();
+        SourcedValue { value, source, metadata }
+    })
+    .filter(|key| !key.metadata.is_expired(now))
+    .collect();
+    let onchain_records = onchain_data.into_iter().enumerate();
+    let mut offchain_records = app.db.get_data(user_id.clone());
+    offchain_records.retain(|record| !record.is_deleted());
+    // Data entries are stored off-chain only, none are submitted to the prover
+    let data = merge_sources(
+        onchain_records.map(|(index, data)| DataRecord::raw(index, data)).collect(),
+        Vec::new(),
+        offchain_records,
+        |record| record.value.as_str(),
+    )
+    .into_iter()
+    .map(|(record, source)| SourcedData { record, source })
+    .collect();
+
+    AccountView {
+        id: user_id,
+        nonce: account.nonce(),
+        keys,
+        data,
+        encoding: BinaryEncoding::default(),
+    }
+}
+
+// Views of all accounts in the off-chain database, skipping accounts whose
+// view cannot be built
+pub async fn list_account_views(app: Arc<AppState>) -> Vec<AccountView> {
+    let mut views = Vec::new();
+    for id in app.db.get_accounts() {
+        match build_account_view(app.clone(), id.clone()).await {
+            Ok(view) => views.push(view),
+            Err(e) => tracing::warn!("Failed to get account for {}: {}", id, e),
+        }
+    }
+    views
+}
+
+// Merge on-chain, pending and off-chain entries, dropping entries whose value
+// already appeared in an earlier group
+fn merge_sources<T>(
+    onchain: Vec<T>,
+    pending: Vec<T>,
+    offchain: Vec<T>,
+    value: impl Fn(&T) -> &str,
+) -> Vec<(T, Source)> {
+    let mut merged: Vec<(T, Source)> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let groups = [
+        (onchain, Source::OnChain),
+        (pending, Source::OffChainPending),
+        (offchain, Source::OffChainOnly),
+    ];
+    for (entries, source) in groups {
+        for entry in entries {
+            if seen.insert(value(&entry).to_string()) {
+                merged.push((entry, source));
+            }
+        }
+    }
+    merged
+}
+
+// Find the ids of the accounts holding the given verifying key on-chain or
+// pending. Off-chain keys are left out since adding them takes no signature of
+// the account. The index can lag behind expiries and revocations, so
+// candidates are checked against their views.
+pub async fn find_accounts_by_key(
+    app: Arc<AppState>,
+    verifying_key: VerifyingKey,
+) -> anyhow::Result<Vec<String>> {
+    let key = verifying_key.to_string();
+    let mut accounts = Vec::new();
+    for id in app.db.get_accounts_by_key(key.clone()) {
+        match build_account_view(app.clone(), id.clone()).await {
+            Ok(view) if view.keys.iter().any(|view_key| holds_key(view_key, &key)) => {
+                accounts.push(id)
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to get account for {}: {}", id, e),
+        }
+    }
+    Ok(accounts)
+}
+
+fn holds_key(view_key: &SourcedValue, key: &str) -> bool {
+    view_key.value == key && view_key.source != Source::OffChainOnly
+}
+
+// Id an existing account is stored under, normalized like ids of new accounts
+// but not checked against the policy, so accounts created before a change of
+// the policy can still be found
+pub fn lookup_account_id(app: &AppState, user_id: &str) -> String {
+    normalize_case(&app.config.account_id, user_id)
+}
+
+// Check an account id against the id policy and the reserved names, returning
+// the normalized id the account is created under
+pub fn validate_account_id(app: Arc<AppState>, user_id: &str) -> anyhow::Result<String> {
+    let id = normalize_account_id(&app.config.account_id, user_id)?;
+    let reserved = app.db.get_reserved_ids();
+    if id.eq_ignore_ascii_case(&app.service_id) || is_reserved(&reserved, &id) {
+        return Err(StatusError::new(StatusCode::CONFLICT, format!("Account id {} is reserved", id))
+            .into());
+    }
+    Ok(id)
 }
 
 // Register service to be able to create accounts
+#[tracing::instrument(skip_all, fields(account_id = %app.service_id))]
 pub async fn register_service(app: Arc<AppState>) -> anyhow::Result<()> {
     // First, we make sure the service is not already registered.
-    if app.prover.clone().get_account(&app.service_id).await?.account.is_some() {
+    if fetch_prover_account(&app, &app.service_id).await?.account.is_some() {
         tracing::info!("Service already registered.");
         return Ok(());
     }
@@ -29,21 +257,53 @@ pub async fn register_service(app: Arc<AppState>) -> anyhow::Result<()> {
         .prover
         .clone()
         .register_service(app.service_id.clone(), vk, &app.service_sk)
+        .instrument(info_span!("prover.register_service"))
         .await?
         .wait()
+        .instrument(info_span!("prover.wait_for_inclusion"))
         .await?;
 
     app.db.insert_account(app.service_id.clone(), account.clone());
+    index_account_keys(app.clone(), app.service_id.clone(), &account);
+    app.db.append_event(
+        app.service_id.clone(),
+        AccountEvent::new(EventKind::Created)
+            .with_signing_key(vk.to_string())
+            .with_key(vk.to_string()),
+    );
+
+    Ok(())
+}
 
+// Make sure another transaction can be queued to the prover without exceeding
+// the global cap on pending transactions. Pending entries that made it
+// on-chain since the reconciler last ran are cleared first.
+pub async fn ensure_pending_capacity(app: Arc<AppState>) -> anyhow::Result<()> {
+    let max_pending = app.config.rate_limit.max_pending_transactions;
+    if app.db.pending_count() < max_pending {
+        return Ok(());
+    }
+    for id in app.db.get_pending_accounts() {
+        refresh_account(app.clone(), id).await?;
+    }
+    if app.db.pending_count() >= max_pending {
+        return Err(StatusError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many pending transactions, try again later",
+        )
+        .into());
+    }
     Ok(())
 }
 
 // Request signing payload to create an account with given user id
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
 pub async fn request_create_account(
     app: Arc<AppState>,
     user_id: String,
     verifying_key: VerifyingKey,
 ) -> anyhow::Result<Vec<u8>> {
+    let user_id = validate_account_id(app.clone(), &user_id)?;
     let bytes_to_be_signed = app
         .prover
         .clone()
@@ -60,16 +320,39 @@ pub async fn request_create_account(
 }
 
 // Send a request to create an account with given user id
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
 pub async fn send_create_account(
     app: Arc<AppState>,
     user_id: String,
     signature_bundle: SignatureBundle,
 ) -> anyhow::Result<Account> {
+    let user_id = validate_account_id(app.clone(), &user_id)?;
     // First, we make sure the account is not already registered.
-    if let Some(account) = app.prover.get_account(&user_id).await?.account {
+    if let Some(account) = fetch_prover_account(&app, &user_id).await?.account {
         tracing::info!("Account {} exists already", &user_id);
+        index_account_keys(app.clone(), user_id, &account);
         return Ok(account);
     }
+    let create_tx = build_create_account(app.clone(), user_id.clone(), signature_bundle)?;
+    submit_create_account(app, create_tx).await
+}
+
+// Signed transaction creating an account, with the account it results in
+pub struct CreateAccountTx {
+    pub user_id: String,
+    pub tx: Transaction,
+    pub tx_id: String,
+    pub key: VerifyingKey,
+    pub account: Account,
+}
+
+// Build the transaction creating an account and check it against a fresh
+// account
+pub fn build_create_account(
+    app: Arc<AppState>,
+    user_id: String,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<CreateAccountTx> {
     let unsigned_tx = app
         .prover
         .clone()
@@ -81,27 +364,95 @@ pub async fn send_create_account(
         .meeting_signed_challenge(&app.service_sk)?
         .transaction();
 
+    let key = signature_bundle.verifying_key.clone();
+    let tx_id = transaction_id(&unsigned_tx.signing_payload()?, &signature_bundle);
     let tx = unsigned_tx.externally_signed(signature_bundle);
 
     let mut account = Account::default();
     account.process_transaction(&tx)?;
 
+    Ok(CreateAccountTx { user_id, tx, tx_id, key, account })
+}
+
+// Queue an account creation to the prover and record it off-chain
+#[tracing::instrument(
+    skip_all,
+    fields(account_id = %create_tx.user_id, tx_id = %create_tx.tx_id),
+)]
+pub async fn submit_create_account(
+    app: Arc<AppState>,
+    create_tx: CreateAccountTx,
+) -> anyhow::Result<Account> {
+    let CreateAccountTx { user_id, tx, tx_id, key, account } = create_tx;
+
+    ensure_pending_capacity(app.clone()).await?;
     tracing::info!("Submitting transaction to create account {}", &user_id);
-    app.prover.clone().validate_and_queue_update(tx.clone()).await?;
+    let span = info_span!("prover.queue_update", account_id = %user_id, tx_id = %tx_id);
+    app.prover.clone().validate_and_queue_update(tx).instrument(span).await?;
 
     app.db.insert_account(user_id.clone(), account.clone());
+    app.db.insert_pending_key(user_id.clone(), key.to_string());
+    let now = now_secs();
+    app.db.update_key_metadata(user_id.clone(), key.to_string(), |metadata| {
+        metadata.created_at = Some(now);
+        metadata.last_used_at = Some(now);
+        // The first key of an account signs its own addition
+        metadata.added_by = Some(key.to_string());
+    });
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(EventKind::Created)
+            .with_tx_id(tx_id)
+            .with_signing_key(key.to_string())
+            .with_key(key.to_string()),
+    );
 
     Ok(account)
 }
 
-// Add a key to an account
+// Manually add an account to the off-chain database
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn add_account(app: Arc<AppState>, user_id: String) -> anyhow::Result<String> {
+    let user_id = validate_account_id(app.clone(), &user_id)?;
+    app.db.insert_account(user_id.clone(), Account::default());
+    app.db.append_event(user_id.clone(), AccountEvent::new(EventKind::Created));
+    Ok(user_id)
+}
+
+// Add the given names to the reserved account ids
+pub fn reserve_account_ids(app: Arc<AppState>, ids: Vec<String>) -> Vec<String> {
+    app.db.insert_reserved_ids(ids);
+    app.db.get_reserved_ids()
+}
+
+// Remove a name from the reserved account ids
+pub fn release_account_id(app: Arc<AppState>, id: String) -> anyhow::Result<Vec<String>> {
+    if !app.db.remove_reserved_id(id.clone()) {
+        return Err(StatusError::not_found(format!("Account id {} is not reserved", id)).into());
+    }
+    Ok(app.db.get_reserved_ids())
+}
+
+// Check that the expiry requested for a new key is in the future
+pub fn validate_key_expiry(expires_at: Option<u64>) -> anyhow::Result<()> {
+    if expires_at.is_some_and(|expires_at| expires_at <= now_secs()) {
+        return Err(StatusError::bad_request("Key expiry must be in the future").into());
+    }
+    Ok(())
+}
+
+// Add a key to an account, valid until the given expiry if any
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
 pub async fn add_key(
     app: Arc<AppState>,
     user_id: String,
     new_key: VerifyingKey,
+    expires_at: Option<u64>,
     // signature_bundle: SignatureBundle,
 ) -> anyhow::Result<Account> {
-    if let Some(account) = app.prover.clone().get_account(&user_id).await?.account {
+    let user_id = lookup_account_id(&app, &user_id);
+    validate_key_expiry(expires_at)?;
+    if let Some(account) = fetch_prover_account(&app, &user_id).await?.account {
         tracing::info!("Submitting transaction to add key to account {}", &user_id);
 
         // let unsigned_tx = app
@@ -118,7 +469,7 @@ pub async fn add_key(
         // tracing::info!("Submitting transaction to add key to account {}", &user_id);
         // app.prover.clone().validate_and_queue_update(tx.clone()).await?;
 
-        app.db.insert_key(user_id.clone(), new_key.to_string());
+        store_key(app.clone(), user_id.clone(), &new_key, expires_at);
 
         return Ok(account);
     };
@@ -126,15 +477,137 @@ pub async fn add_key(
     Err(anyhow!("Account {} not found", &user_id))
 }
 
+// Revoke an off-chain key of an account. Keys that are on-chain or pending
+// inclusion can only be revoked by a transaction signed by the account.
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn revoke_key(
+    app: Arc<AppState>,
+    user_id: String,
+    key: VerifyingKey,
+) -> anyhow::Result<()> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let account = fetch_prover_account(&app, &user_id).await?.account.unwrap_or_default();
+    let key = key.to_string();
+    let onchain = account.valid_keys().iter().any(|valid_key| valid_key.to_string() == key);
+    if onchain || app.db.get_pending_keys(user_id.clone()).contains(&key) {
+        return Err(StatusError::new(
+            StatusCode::CONFLICT,
+            "On-chain keys can only be revoked by a transaction signed by the account",
+        )
+        .into());
+    }
+    if !app.db.remove_key(user_id.clone(), key.clone()) {
+        return Err(StatusError::not_found(format!("Key not found for account {}", user_id)).into());
+    }
+    app.db.remove_key_metadata(user_id.clone(), &key);
+
+    app.db.append_event(user_id.clone(), AccountEvent::new(EventKind::KeyRevoked).with_key(key));
+    tracing::info!("Revoked off-chain key of account {}", &user_id);
+    Ok(())
+}
+
+// Expired keys of an account waiting for a revoke transaction
+pub fn list_pending_revocations(app: Arc<AppState>, user_id: String) -> Vec<PendingRevocation> {
+    let user_id = lookup_account_id(&app, &user_id);
+    app.db.get_pending_revocations(user_id)
+}
+
+// Build the transaction revoking an expired on-chain key of an account
+async fn build_revoke_expired_key(
+    app: &Arc<AppState>,
+    user_id: &str,
+    key: &VerifyingKey,
+) -> anyhow::Result<UnsignedTransaction> {
+    let queued = app.db.get_pending_revocations(user_id.to_string());
+    if !queued.iter().any(|revocation| revocation.key == key.to_string()) {
+        let message = format!("No expired key to revoke for account {}", user_id);
+        return Err(StatusError::not_found(message).into());
+    }
+    let Some(account) = fetch_prover_account(app, user_id).await?.account else {
+        return Err(StatusError::not_found(format!("Account {} not found", user_id)).into());
+    };
+    let unsigned_tx = app
+        .prover
+        .clone()
+        .build_request()
+        .to_modify_account(&account)
+        .revoke_key(key.clone())?
+        .transaction();
+    Ok(unsigned_tx)
+}
+
+// Request signing payload to revoke an expired on-chain key of an account
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn request_revoke_expired_key(
+    app: Arc<AppState>,
+    user_id: String,
+    key: VerifyingKey,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let unsigned_tx = build_revoke_expired_key(&app, &user_id, &key).await?;
+    Ok(unsigned_tx.signing_payload()?)
+}
+
+// Send the transaction revoking an expired on-chain key of an account, signed
+// by one of the account's keys
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn send_revoke_expired_key(
+    app: Arc<AppState>,
+    user_id: String,
+    key: VerifyingKey,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<PendingRevocation> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let unsigned_tx = build_revoke_expired_key(&app, &user_id, &key).await?;
+    let Some(account) = fetch_prover_account(&app, &user_id).await?.account else {
+        return Err(StatusError::not_found(format!("Account {} not found", &user_id)).into());
+    };
+    // The expired key may sign its own revocation, as the prover still
+    // accepts it
+    if !account.valid_keys().contains(&signature_bundle.verifying_key) {
+        let message = "Signing key is not a key of the account";
+        return Err(StatusError::new(StatusCode::FORBIDDEN, message).into());
+    }
+    let payload = unsigned_tx.signing_payload()?;
+    signature_bundle
+        .verifying_key
+        .verify_signature(&payload, &signature_bundle.signature)
+        .map_err(|e| StatusError::bad_request(format!("Invalid signature: {}", e)))?;
+
+    ensure_pending_capacity(app.clone()).await?;
+    let signing_key = signature_bundle.verifying_key.to_string();
+    let tx_id = transaction_id(&payload, &signature_bundle);
+    let tx = unsigned_tx.externally_signed(signature_bundle);
+    tracing::info!("Submitting transaction to revoke expired key of account {}", &user_id);
+    let span = info_span!("prover.queue_update", account_id = %user_id, tx_id = %tx_id);
+    app.prover.clone().validate_and_queue_update(tx).instrument(span).await?;
+
+    let key = key.to_string();
+    let revocation = app
+        .db
+        .set_revocation_tx(user_id.clone(), &key, tx_id.clone())
+        .ok_or_else(|| anyhow!("Revocation of key of account {} not found", &user_id))?;
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(EventKind::KeyRevoked)
+            .with_tx_id(tx_id)
+            .with_signing_key(signing_key)
+            .with_key(key),
+    );
+    Ok(revocation)
+}
+
 // Add data to an account
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
 pub async fn add_data(
     app: Arc<AppState>,
     user_id: String,
-    data: String,
+    data: NewData,
     // data_signature: SignatureBundle,
     // signature_bundle: SignatureBundle,
 ) -> anyhow::Result<Account> {
-    if let Some(account) = app.prover.clone().get_account(&user_id).await?.account {
+    let user_id = lookup_account_id(&app, &user_id);
+    if let Some(account) = fetch_prover_account(&app, &user_id).await?.account {
         tracing::info!("Submitting transaction to add data to account {}", &user_id);
         // let unsigned_tx = app
         //     .prover
@@ -148,7 +621,7 @@ pub async fn add_data(
 
         // tracing::info!("Submitting transaction to add data to account {}", &user_id);
         // app.prover.clone().validate_and_queue_update(tx.clone()).await?;
-        app.db.insert_data(user_id.clone(), data.clone());
+        store_data(app.clone(), user_id.clone(), data, None)?;
 
         return Ok(account);
     };
@@ -156,23 +629,1199 @@ pub async fn add_data(
     Err(anyhow!("Account {} not found", &user_id))
 }
 
+fn is_key_expired(app: &AppState, account_id: &str, key: &str, now: u64) -> bool {
+    let key_metadata = app.db.get_key_metadata(account_id.to_string());
+    key_metadata.get(key).is_some_and(|metadata| metadata.is_expired(now))
+}
+
+// Whether a key is an on-chain key of an account that has not expired
+fn is_valid_account_key(
+    app: &AppState,
+    account_id: &str,
+    account: &Account,
+    key: &VerifyingKey,
+) -> bool {
+    account.valid_keys().contains(key)
+        && !is_key_expired(app, account_id, &key.to_string(), now_secs())
+}
+
+// Record that a key of an account signed a request accepted by the service
+fn record_key_use(app: &AppState, account_id: &str, key: &VerifyingKey) {
+    app.db.update_key_metadata(account_id.to_string(), key.to_string(), |metadata| {
+        metadata.last_used_at = Some(now_secs());
+    });
+}
+
+// Check a change to the metadata of a key of an account
+async fn validate_key_metadata_update(
+    app: &Arc<AppState>,
+    user_id: &str,
+    key: &VerifyingKey,
+    update: &KeyMetadataUpdate,
+) -> anyhow::Result<()> {
+    let too_long = [&update.label, &update.wallet_type]
+        .into_iter()
+        .flatten()
+        .any(|field| field.len() > MAX_METADATA_FIELD_LEN);
+    if too_long {
+        let message =
+            format!("Key metadata fields are limited to {} bytes", MAX_METADATA_FIELD_LEN);
+        return Err(StatusError::bad_request(message).into());
+    }
+    let view = build_account_view(app.clone(), user_id.to_string()).await?;
+    let key = key.to_string();
+    if !view.keys.iter().any(|account_key| account_key.value == key) {
+        return Err(StatusError::not_found(format!("Key not found for account {}", user_id)).into());
+    }
+    Ok(())
+}
+
+fn key_metadata_revision(app: &AppState, user_id: &str, key: &str) -> u64 {
+    let key_metadata = app.db.get_key_metadata(user_id.to_string());
+    key_metadata.get(key).map_or(0, |metadata| metadata.revision)
+}
+
+// Request the payload an account key has to sign to change the metadata of a
+// key of the account
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn request_key_metadata_update(
+    app: Arc<AppState>,
+    user_id: String,
+    key: VerifyingKey,
+    update: KeyMetadataUpdate,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    validate_key_metadata_update(&app, &user_id, &key, &update).await?;
+    let key = key.to_string();
+    let revision = key_metadata_revision(&app, &user_id, &key);
+    Ok(key_metadata_payload(&app.service_id, &user_id, &key, &update, revision))
+}
+
+// Set the user managed metadata of a key of an account with a signature of one
+// of the account's on-chain keys
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn update_key_metadata(
+    app: Arc<AppState>,
+    user_id: String,
+    key: VerifyingKey,
+    update: KeyMetadataUpdate,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<KeyMetadata> {
+    let user_id = lookup_account_id(&app, &user_id);
+    validate_key_metadata_update(&app, &user_id, &key, &update).await?;
+    let key = key.to_string();
+    let revision = key_metadata_revision(&app, &user_id, &key);
+    let payload = key_metadata_payload(&app.service_id, &user_id, &key, &update, revision);
+    verify_account_signature(&app, &user_id, &payload, &signature_bundle).await?;
+
+    // A concurrent change may have used the same revision
+    let mut replayed = false;
+    let metadata = app.db.update_key_metadata(user_id, key, |metadata| {
+        if metadata.revision == revision {
+            update.apply(metadata);
+        } else {
+            replayed = true;
+        }
+    });
+    if replayed {
+        let message = "The key metadata changed since the payload was signed";
+        return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+    }
+    Ok(metadata)
+}
+
+// Store an off-chain key for an account. No key of the account signs the
+// addition, so the service key is recorded as having added it.
+pub fn store_key(
+    app: Arc<AppState>,
+    user_id: String,
+    new_key: &VerifyingKey,
+    expires_at: Option<u64>,
+) {
+    app.db.insert_key(user_id.clone(), new_key.to_string());
+    let service_key = app.service_sk.verifying_key().to_string();
+    app.db.update_key_metadata(user_id.clone(), new_key.to_string(), |metadata| {
+        metadata.created_at = Some(now_secs());
+        metadata.expires_at = expires_at;
+        metadata.added_by = Some(service_key);
+    });
+    let event = AccountEvent::new(EventKind::KeyAdded).with_key(new_key.to_string());
+    app.db.append_event(user_id, event);
+}
+
+// Validate and store an off-chain data entry for an account, recording the key
+// that signed it, if any
+pub fn store_data(
+    app: Arc<AppState>,
+    user_id: String,
+    data: NewData,
+    signing_key: Option<String>,
+) -> anyhow::Result<DataRecord> {
+    validate_new_data(app.clone(), &data)?;
+    let record = DataRecord {
+        id: Uuid::new_v4().to_string(),
+        revision: 1,
+        type_tag: data.type_tag,
+        content_type: data.content_type,
+        created_at: Some(now_secs()),
+        schema: data.schema,
+        value: data.value,
+        updated_at: None,
+        deleted_at: None,
+    };
+    let limits = &app.config.limits;
+    app.db.try_insert_data(user_id.clone(), record.clone(), |existing| {
+        let live: Vec<&DataRecord> = existing.iter().filter(|r| !r.is_deleted()).collect();
+        check_data_quota(limits, &live, record.value.len())
+    })?;
+    let mut event = AccountEvent::new(EventKind::DataAdded).with_data_id(record.id.clone());
+    event.signing_key = signing_key;
+    app.db.append_event(user_id, event);
+    Ok(record)
+}
+
+// Check a new data entry against the size limits and its schema, if any
+pub fn validate_new_data(app: Arc<AppState>, data: &NewData) -> anyhow::Result<()> {
+    // The raw type marks on-chain data, whose values are base64
+    if data.type_tag == RAW_DATA_TYPE {
+        let message = format!("Data type {} is reserved for on-chain data", RAW_DATA_TYPE);
+        return Err(StatusError::bad_request(message).into());
+    }
+    let limits = &app.config.limits;
+    if data.value.len() > limits.max_data_item_bytes {
+        return Err(StatusError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Data entry exceeds {} bytes", limits.max_data_item_bytes),
+        )
+        .into());
+    }
+
+    if let Some(schema_id) = &data.schema {
+        let (_, schema) = app
+            .db
+            .get_schema(schema_id.clone())
+            .ok_or_else(|| StatusError::not_found(format!("Schema {} not found", schema_id)))?;
+        validate_value(&schema, &data.value)?;
+    }
+
+    Ok(())
+}
+
+// Request the payload an account key has to sign to change one of the
+// account's data entries
+#[tracing::instrument(skip_all, fields(account_id = %user_id, entry_id = %entry_id))]
+pub async fn request_data_change(
+    app: Arc<AppState>,
+    user_id: String,
+    entry_id: String,
+    change: DataChange,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let record = find_changeable_data(app.clone(), &user_id, &entry_id)?;
+    Ok(data_change_payload(&app.service_id, &user_id, &entry_id, record.revision, &change))
+}
+
+// Replace or delete a data entry of an account, authorized by a signature of
+// one of the account's on-chain keys over the change payload.
+// Prism accounts only support appending data, so on-chain entries cannot be
+// changed; deletions leave an off-chain tombstone.
+#[tracing::instrument(skip_all, fields(account_id = %user_id, entry_id = %entry_id))]
+pub async fn send_data_change(
+    app: Arc<AppState>,
+    user_id: String,
+    entry_id: String,
+    change: DataChange,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<DataRecord> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let Some(account) = fetch_prover_account(&app, &user_id).await?.account else {
+        return Err(StatusError::not_found(format!("Account {} not found", &user_id)).into());
+    };
+    let signed_by_account =
+        is_valid_account_key(&app, &user_id, &account, &signature_bundle.verifying_key);
+    if !signed_by_account {
+        // Otherwise the change has to be allowed to a session key of the
+        // account, for both the current and the new type of the entry
+        let key = signature_bundle.verifying_key.to_string();
+        let record = find_changeable_data(app.clone(), &user_id, &entry_id)?;
+        let operation = SessionOperation::ChangeData;
+        authorize_session(&app, &user_id, &account, &key, operation, &record.type_tag, None)?;
+        if let DataChange::Replace(data) = &change {
+            authorize_session(&app, &user_id, &account, &key, operation, &data.type_tag, None)?;
+        }
+    }
+    if let DataChange::Replace(data) = &change {
+        validate_new_data(app.clone(), data)?;
+    }
+
+    let limits = &app.config.limits;
+    let service_id = app.service_id.clone();
+    let record = app.db.update_data(user_id.clone(), &entry_id, |record, existing| {
+        if record.is_deleted() {
+            return Err(StatusError::not_found(format!("Data entry {} not found", entry_id)).into());
+        }
+        // The revision is re-read under the lock so a change signed for an
+        // older revision is rejected
+        let payload =
+            data_change_payload(&service_id, &user_id, &entry_id, record.revision, &change);
+        signature_bundle
+            .verifying_key
+            .verify_signature(&payload, &signature_bundle.signature)
+            .map_err(|e| StatusError::bad_request(format!("Invalid signature: {}", e)))?;
+
+        let now = now_secs();
+        record.revision += 1;
+        record.updated_at = Some(now);
+        match &change {
+            DataChange::Replace(data) => {
+                let others: Vec<&DataRecord> = existing
+                    .iter()
+                    .filter(|other| other.id != record.id && !other.is_deleted())
+                    .collect();
+                // The entry keeps its slot, so only the size quota applies
+                let used: usize = others.iter().map(|other| other.value.len()).sum();
+                if used + data.value.len() > limits.max_data_bytes_per_account {
+                    return Err(StatusError::new(
+                        StatusCode::FORBIDDEN,
+                        format!(
+                            "Account data would exceed {} bytes",
+                            limits.max_data_bytes_per_account
+                        ),
+                    )
+                    .into());
+                }
+                record.type_tag = data.type_tag.clone();
+                record.content_type = data.content_type.clone();
+                record.schema = data.schema.clone();
+                record.value = data.value.clone();
+            }
+            DataChange::Delete => {
+                record.value = String::new();
+                record.deleted_at = Some(now);
+            }
+        }
+        Ok(())
+    })?;
+
+    let kind = match change {
+        DataChange::Replace(_) => EventKind::DataUpdated,
+        DataChange::Delete => EventKind::DataDeleted,
+    };
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(kind)
+            .with_signing_key(signature_bundle.verifying_key.to_string())
+            .with_data_id(entry_id.clone()),
+    );
+    if signed_by_account {
+        record_key_use(&app, &user_id, &signature_bundle.verifying_key);
+    }
+
+    tracing::info!("Changed data entry {} of account {}", &entry_id, &user_id);
+    Ok(record)
+}
+
+// Check the permissions and expiry requested for a session key
+fn validate_session_grant(
+    app: &AppState,
+    permissions: &[SessionPermission],
+    expires_at: u64,
+) -> anyhow::Result<()> {
+    if permissions.is_empty() {
+        return Err(StatusError::bad_request("Session keys need at least one permission").into());
+    }
+    let now = now_secs();
+    let max_session_secs = app.config.limits.max_session_secs;
+    if expires_at <= now || expires_at > now + max_session_secs {
+        let message = format!("Session keys must expire within {} seconds", max_session_secs);
+        return Err(StatusError::bad_request(message).into());
+    }
+    Ok(())
+}
+
+// Request the payload an account key has to sign to authorize a session key
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn request_session_key(
+    app: Arc<AppState>,
+    user_id: String,
+    session_key: VerifyingKey,
+    permissions: Vec<SessionPermission>,
+    expires_at: u64,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    validate_session_grant(&app, &permissions, expires_at)?;
+    let session_key = session_key.to_string();
+    Ok(session_grant_payload(&app.service_id, &user_id, &session_key, &permissions, expires_at))
+}
+
+// Authorize a session key with a signature of one of the account's on-chain
+// keys over the grant payload
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn send_session_key(
+    app: Arc<AppState>,
+    user_id: String,
+    session_key: VerifyingKey,
+    permissions: Vec<SessionPermission>,
+    expires_at: u64,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<SessionKey> {
+    let user_id = lookup_account_id(&app, &user_id);
+    validate_session_grant(&app, &permissions, expires_at)?;
+    let Some(account) = fetch_prover_account(&app, &user_id).await?.account else {
+        return Err(StatusError::not_found(format!("Account {} not found", &user_id)).into());
+    };
+    if !is_valid_account_key(&app, &user_id, &account, &signature_bundle.verifying_key) {
+        return Err(StatusError::new(
+            StatusCode::FORBIDDEN,
+            format!("Key is not a valid key of account {}", &user_id),
+        )
+        .into());
+    }
+    let session_key = session_key.to_string();
+    let payload =
+        session_grant_payload(&app.service_id, &user_id, &session_key, &permissions, expires_at);
+    signature_bundle
+        .verifying_key
+        .verify_signature(&payload, &signature_bundle.signature)
+        .map_err(|e| StatusError::bad_request(format!("Invalid signature: {}", e)))?;
+
+    let authorized_by = signature_bundle.verifying_key.to_string();
+    let session = SessionKey {
+        verifying_key: session_key.clone(),
+        permissions,
+        expires_at,
+        authorized_by: authorized_by.clone(),
+        created_at: now_secs(),
+        last_nonce: 0,
+        revoked_at: None,
+    };
+    if !app.db.insert_session(user_id.clone(), session.clone()) {
+        return Err(StatusError::new(
+            StatusCode::CONFLICT,
+            "Session key was already authorized for this account",
+        )
+        .into());
+    }
+    record_key_use(&app, &user_id, &signature_bundle.verifying_key);
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(EventKind::SessionCreated)
+            .with_signing_key(authorized_by)
+            .with_key(session_key),
+    );
+    tracing::info!("Authorized session key for account {}", &user_id);
+    Ok(session)
+}
+
+// Session keys authorized by an account, including expired and revoked ones
+pub fn list_session_keys(app: Arc<AppState>, user_id: String) -> Vec<SessionKey> {
+    let user_id = lookup_account_id(&app, &user_id);
+    app.db.get_sessions(user_id)
+}
+
+// Revoke a session key of an account before it expires
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub fn revoke_session_key(
+    app: Arc<AppState>,
+    user_id: String,
+    session_key: VerifyingKey,
+) -> anyhow::Result<SessionKey> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let key = session_key.to_string();
+    let session = app
+        .db
+        .update_session(user_id.clone(), &key, |session| {
+            if session.revoked_at.is_some() {
+                let message = "Session key is already revoked";
+                return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+            }
+            session.revoked_at = Some(now_secs());
+            Ok(())
+        })
+        .ok_or_else(|| {
+            StatusError::not_found(format!("Session key not found for account {}", user_id))
+        })??;
+    app.db.append_event(user_id, AccountEvent::new(EventKind::SessionRevoked).with_key(key));
+    Ok(session)
+}
+
+// Check that a session key of an account is active and allowed to perform the
+// operation on data of the given type. The session lapses with the account key
+// that authorized it, once that key is revoked or expires. A nonce, when
+// given, has to be higher than the last one used by the key and is recorded.
+fn authorize_session(
+    app: &AppState,
+    user_id: &str,
+    account: &Account,
+    key: &str,
+    operation: SessionOperation,
+    type_tag: &str,
+    nonce: Option<u64>,
+) -> anyhow::Result<()> {
+    let now = now_secs();
+    let valid_keys: Vec<String> = account
+        .valid_keys()
+        .iter()
+        .map(|key| key.to_string())
+        .filter(|key| !is_key_expired(app, user_id, key, now))
+        .collect();
+    let authorized = app.db.update_session(user_id.to_string(), key, |session| {
+        if !session.is_active(now) {
+            let message = "Session key is expired or revoked";
+            return Err(StatusError::new(StatusCode::FORBIDDEN, message).into());
+        }
+        if !valid_keys.contains(&session.authorized_by) {
+            let message = "Key that authorized the session key is no longer valid";
+            return Err(StatusError::new(StatusCode::FORBIDDEN, message).into());
+        }
+        if !session.allows(operation, type_tag) {
+            let message = format!("Session key is not allowed this operation on {}", type_tag);
+            return Err(StatusError::new(StatusCode::FORBIDDEN, message).into());
+        }
+        if let Some(nonce) = nonce {
+            if nonce <= session.last_nonce {
+                let message = format!("Nonce must be higher than {}", session.last_nonce);
+                return Err(StatusError::bad_request(message).into());
+            }
+            session.last_nonce = nonce;
+        }
+        Ok(())
+    });
+    match authorized {
+        Some(authorized) => authorized.map(|_| ()),
+        None => Err(StatusError::new(
+            StatusCode::FORBIDDEN,
+            format!("Key is not a valid key of account {}", user_id),
+        )
+        .into()),
+    }
+}
+
+// Request the payload a session key has to sign to add a data entry
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn request_session_data(
+    app: Arc<AppState>,
+    user_id: String,
+    data: NewData,
+    nonce: u64,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    validate_new_data(app.clone(), &data)?;
+    Ok(session_data_payload(&app.service_id, &user_id, nonce, &data))
+}
+
+// Add a data entry to an account, authorized by a signature of one of the
+// account's session keys over the entry and a nonce
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn add_session_data(
+    app: Arc<AppState>,
+    user_id: String,
+    data: NewData,
+    nonce: u64,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<DataRecord> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let Some(account) = fetch_prover_account(&app, &user_id).await?.account else {
+        return Err(StatusError::not_found(format!("Account {} not found", &user_id)).into());
+    };
+    validate_new_data(app.clone(), &data)?;
+    let payload = session_data_payload(&app.service_id, &user_id, nonce, &data);
+    signature_bundle
+        .verifying_key
+        .verify_signature(&payload, &signature_bundle.signature)
+        .map_err(|e| StatusError::bad_request(format!("Invalid signature: {}", e)))?;
+
+    let key = signature_bundle.verifying_key.to_string();
+    let operation = SessionOperation::AddData;
+    authorize_session(&app, &user_id, &account, &key, operation, &data.type_tag, Some(nonce))?;
+    let record = store_data(app, user_id.clone(), data, Some(key))?;
+    tracing::info!("Added data entry {} to account {} with a session key", &record.id, &user_id);
+    Ok(record)
+}
+
+// Check that a payload is signed by one of the on-chain keys of an account
+async fn verify_account_signature(
+    app: &AppState,
+    account_id: &str,
+    payload: &[u8],
+    signature_bundle: &SignatureBundle,
+) -> anyhow::Result<()> {
+    let account = fetch_prover_account(app, account_id).await?.account.unwrap_or_default();
+    if !is_valid_account_key(app, account_id, &account, &signature_bundle.verifying_key) {
+        return Err(StatusError::new(
+            StatusCode::FORBIDDEN,
+            format!("Key is not a valid key of account {}", account_id),
+        )
+        .into());
+    }
+    signature_bundle
+        .verifying_key
+        .verify_signature(payload, &signature_bundle.signature)
+        .map_err(|e| StatusError::bad_request(format!("Invalid signature: {}", e)))?;
+    record_key_use(app, account_id, &signature_bundle.verifying_key);
+    Ok(())
+}
+
+// Check the guardians and threshold requested for an account
+fn validate_recovery_setup(
+    app: &AppState,
+    user_id: &str,
+    guardians: &[String],
+    threshold: usize,
+) -> anyhow::Result<()> {
+    let max_guardians = app.config.recovery.max_guardians;
+    if guardians.is_empty() || guardians.len() > max_guardians {
+        let message = format!("Accounts need between 1 and {} guardians", max_guardians);
+        return Err(StatusError::bad_request(message).into());
+    }
+    if remove_duplicates(guardians.to_vec()).len() < guardians.len() {
+        return Err(StatusError::bad_request("Guardians must be distinct accounts").into());
+    }
+    if guardians.iter().any(|guardian| guardian == user_id) {
+        return Err(StatusError::bad_request("An account cannot be its own guardian").into());
+    }
+    if threshold == 0 || threshold > guardians.len() {
+        let message = "The threshold must be between 1 and the number of guardians";
+        return Err(StatusError::bad_request(message).into());
+    }
+    Ok(())
+}
+
+fn next_recovery_revision(app: &AppState, user_id: &str) -> u64 {
+    app.db.get_recovery_setup(user_id.to_string()).map_or(1, |setup| setup.revision + 1)
+}
+
+// Request the payload an account key has to sign to set the account's
+// guardians
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn request_recovery_setup(
+    app: Arc<AppState>,
+    user_id: String,
+    guardians: Vec<String>,
+    threshold: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let guardians: Vec<String> =
+        guardians.iter().map(|guardian| lookup_account_id(&app, guardian)).collect();
+    validate_recovery_setup(&app, &user_id, &guardians, threshold)?;
+    let revision = next_recovery_revision(&app, &user_id);
+    Ok(recovery_setup_payload(&app.service_id, &user_id, &guardians, threshold, revision))
+}
+
+// Set the guardians of an account with a signature of one of its on-chain keys
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn send_recovery_setup(
+    app: Arc<AppState>,
+    user_id: String,
+    guardians: Vec<String>,
+    threshold: usize,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<RecoverySetup> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let guardians: Vec<String> =
+        guardians.iter().map(|guardian| lookup_account_id(&app, guardian)).collect();
+    validate_recovery_setup(&app, &user_id, &guardians, threshold)?;
+    for guardian in &guardians {
+        if fetch_prover_account(&app, guardian).await?.account.is_none() {
+            let message = format!("Guardian account {} not found", guardian);
+            return Err(StatusError::not_found(message).into());
+        }
+    }
+    let revision = next_recovery_revision(&app, &user_id);
+    let payload =
+        recovery_setup_payload(&app.service_id, &user_id, &guardians, threshold, revision);
+    // Without the service key the service could not add a recovered key
+    let account = fetch_prover_account(&app, &user_id).await?.account.unwrap_or_default();
+    if !has_service_key(&app, &user_id, &account) {
+        let message = "Enable recovery for the account before setting its guardians";
+        return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+    }
+    verify_account_signature(&app, &user_id, &payload, &signature_bundle).await?;
+    // Guardians cannot be swapped under a recovery that is collecting approvals
+    app.db.expire_recoveries(user_id.clone(), now_secs());
+    let recoveries = app.db.get_recoveries(user_id.clone());
+    if recoveries.iter().any(|recovery| recovery.status == RecoveryStatus::Pending) {
+        let message = "A recovery of the account is pending";
+        return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+    }
+
+    let setup = RecoverySetup { guardians, threshold, revision, updated_at: now_secs() };
+    app.db.set_recovery_setup(user_id.clone(), setup.clone());
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(EventKind::RecoveryConfigured)
+            .with_signing_key(signature_bundle.verifying_key.to_string()),
+    );
+    tracing::info!("Set {} guardians for account {}", setup.guardians.len(), &user_id);
+    Ok(setup)
+}
+
+// Guardians and recoveries of an account
+pub fn get_recovery(app: Arc<AppState>, user_id: String) -> AccountRecovery {
+    let user_id = lookup_account_id(&app, &user_id);
+    app.db.expire_recoveries(user_id.clone(), now_secs());
+    AccountRecovery {
+        setup: app.db.get_recovery_setup(user_id.clone()),
+        recoveries: app.db.get_recoveries(user_id),
+    }
+}
+
+fn find_recovery(
+    app: &AppState,
+    user_id: &str,
+    recovery_id: &str,
+) -> anyhow::Result<RecoveryRequest> {
+    app.db.expire_recoveries(user_id.to_string(), now_secs());
+    let recovery = app
+        .db
+        .get_recoveries(user_id.to_string())
+        .into_iter()
+        .find(|recovery| recovery.id == recovery_id)
+        .ok_or_else(|| StatusError::not_found(format!("Recovery {} not found", recovery_id)))?;
+    Ok(recovery)
+}
+
+fn find_pending_recovery(
+    app: &AppState,
+    user_id: &str,
+    recovery_id: &str,
+) -> anyhow::Result<RecoveryRequest> {
+    let recovery = find_recovery(app, user_id, recovery_id)?;
+    ensure_recovery_pending(&recovery)?;
+    Ok(recovery)
+}
+
+fn ensure_recovery_pending(recovery: &RecoveryRequest) -> anyhow::Result<()> {
+    if recovery.status != RecoveryStatus::Pending {
+        let message = format!("Recovery {} is no longer pending", recovery.id);
+        return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+    }
+    Ok(())
+}
+
+// Guardians of an account, checking that the given account is one of them
+fn guardian_setup(app: &AppState, user_id: &str, guardian: &str) -> anyhow::Result<RecoverySetup> {
+    let setup = app.db.get_recovery_setup(user_id.to_string()).ok_or_else(|| {
+        StatusError::not_found(format!("Account {} has no guardians", user_id))
+    })?;
+    if !setup.guardians.iter().any(|id| id == guardian) {
+        let message = format!("{} is not a guardian of account {}", guardian, user_id);
+        return Err(StatusError::new(StatusCode::FORBIDDEN, message).into());
+    }
+    Ok(setup)
+}
+
+// Request the payload a key of a guardian has to sign to start the recovery of
+// an account with the given key
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn request_recovery_start(
+    app: Arc<AppState>,
+    user_id: String,
+    new_key: VerifyingKey,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let setup = app.db.get_recovery_setup(user_id.clone()).ok_or_else(|| {
+        StatusError::not_found(format!("Account {} has no guardians", &user_id))
+    })?;
+    let sequence = app.db.get_recoveries(user_id.clone()).len();
+    let new_key = new_key.to_string();
+    Ok(recovery_start_payload(&app.service_id, &user_id, &new_key, setup.revision, sequence))
+}
+
+// Start the recovery of an account that lost its keys, to add the given key
+// once enough guardians approved it. The recovery is started by a guardian
+// with a signature of one of its on-chain keys, which counts as its approval,
+// and replaces pending recoveries that have not reached their threshold.
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn start_recovery(
+    app: Arc<AppState>,
+    user_id: String,
+    new_key: VerifyingKey,
+    guardian: String,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<RecoveryRequest> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let guardian = lookup_account_id(&app, &guardian);
+    let setup = guardian_setup(&app, &user_id, &guardian)?;
+    if fetch_prover_account(&app, &user_id).await?.account.is_none() {
+        return Err(StatusError::not_found(format!("Account {} not found", &user_id)).into());
+    }
+    let new_key = new_key.to_string();
+    let sequence = app.db.get_recoveries(user_id.clone()).len();
+    let payload =
+        recovery_start_payload(&app.service_id, &user_id, &new_key, setup.revision, sequence);
+    verify_account_signature(&app, &guardian, &payload, &signature_bundle).await?;
+
+    let now = now_secs();
+    let config = &app.config.recovery;
+    let signing_key = signature_bundle.verifying_key.to_string();
+    let recovery = RecoveryRequest {
+        id: Uuid::new_v4().to_string(),
+        new_key,
+        status: RecoveryStatus::Pending,
+        started_by: guardian.clone(),
+        approvals: vec![GuardianApproval {
+            guardian: guardian.clone(),
+            signing_key: signing_key.clone(),
+            approved_at: now,
+        }],
+        created_at: now,
+        expires_at: now + config.approval_window_secs,
+        executable_at: (setup.threshold <= 1).then_some(now + config.timelock_secs),
+        tx_id: None,
+        updated_at: now,
+    };
+    app.db.expire_recoveries(user_id.clone(), now);
+    let replaced =
+        app.db.insert_recovery(user_id.clone(), recovery.clone(), sequence).ok_or_else(|| {
+            let message = "Another recovery of the account was started or is approved";
+            StatusError::new(StatusCode::CONFLICT, message)
+        })?;
+    for replaced_id in replaced {
+        tracing::info!("Replaced recovery {} of account {}", replaced_id, &user_id);
+    }
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(EventKind::RecoveryStarted)
+            .with_signing_key(signing_key)
+            .with_key(recovery.new_key.clone()),
+    );
+    tracing::info!("Guardian {} started recovery {} of {}", &guardian, &recovery.id, &user_id);
+    Ok(recovery)
+}
+
+// Request the payload a key of a guardian has to sign to approve a recovery
+pub fn request_recovery_approval(
+    app: Arc<AppState>,
+    user_id: String,
+    recovery_id: String,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let recovery = find_pending_recovery(&app, &user_id, &recovery_id)?;
+    Ok(recovery_approval_payload(&app.service_id, &user_id, &recovery))
+}
+
+// Approve a recovery with a signature of one of the on-chain keys of a
+// guardian. The time lock starts once the threshold of approvals is reached.
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn approve_recovery(
+    app: Arc<AppState>,
+    user_id: String,
+    recovery_id: String,
+    guardian: String,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<RecoveryRequest> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let guardian = lookup_account_id(&app, &guardian);
+    let setup = guardian_setup(&app, &user_id, &guardian)?;
+    let recovery = find_pending_recovery(&app, &user_id, &recovery_id)?;
+    let payload = recovery_approval_payload(&app.service_id, &user_id, &recovery);
+    verify_account_signature(&app, &guardian, &payload, &signature_bundle).await?;
+
+    let signing_key = signature_bundle.verifying_key.to_string();
+    let timelock_secs = app.config.recovery.timelock_secs;
+    let recovery = app
+        .db
+        .update_recovery(user_id.clone(), &recovery_id, |recovery| {
+            ensure_recovery_pending(recovery)?;
+            if recovery.approvals.iter().any(|approval| approval.guardian == guardian) {
+                let message = format!("{} already approved the recovery", &guardian);
+                return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+            }
+            let now = now_secs();
+            if recovery.is_expired(now) {
+                let message = format!("Recovery {} has expired", recovery.id);
+                return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+            }
+            recovery.approvals.push(GuardianApproval {
+                guardian: guardian.clone(),
+                signing_key: signing_key.clone(),
+                approved_at: now,
+            });
+            if recovery.approvals.len() >= setup.threshold && recovery.executable_at.is_none() {
+                recovery.executable_at = Some(now + timelock_secs);
+            }
+            recovery.updated_at = now;
+            Ok(())
+        })
+        .ok_or_else(|| StatusError::not_found(format!("Recovery {} not found", recovery_id)))??;
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(EventKind::RecoveryApproved).with_signing_key(signing_key),
+    );
+    tracing::info!("Guardian {} approved recovery {}", &guardian, &recovery_id);
+    Ok(recovery)
+}
+
+// Request the payload a key of the account has to sign to cancel a recovery
+pub fn request_recovery_cancel(
+    app: Arc<AppState>,
+    user_id: String,
+    recovery_id: String,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let recovery = find_pending_recovery(&app, &user_id, &recovery_id)?;
+    Ok(recovery_cancel_payload(&app.service_id, &user_id, &recovery))
+}
+
+// Cancel a pending recovery with a signature of one of the account's valid
+// on-chain keys
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn cancel_recovery(
+    app: Arc<AppState>,
+    user_id: String,
+    recovery_id: String,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<RecoveryRequest> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let recovery = find_pending_recovery(&app, &user_id, &recovery_id)?;
+    let payload = recovery_cancel_payload(&app.service_id, &user_id, &recovery);
+    verify_account_signature(&app, &user_id, &payload, &signature_bundle).await?;
+
+    let recovery = app
+        .db
+        .update_recovery(user_id.clone(), &recovery_id, |recovery| {
+            ensure_recovery_pending(recovery)?;
+            recovery.status = RecoveryStatus::Cancelled;
+            recovery.updated_at = now_secs();
+            Ok(())
+        })
+        .ok_or_else(|| StatusError::not_found(format!("Recovery {} not found", recovery_id)))??;
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(EventKind::RecoveryCancelled)
+            .with_signing_key(signature_bundle.verifying_key.to_string()),
+    );
+    tracing::info!("Cancelled recovery {} of account {}", &recovery_id, &user_id);
+    Ok(recovery)
+}
+
+// Whether the service key was added to an account, on-chain or pending
+fn has_service_key(app: &AppState, user_id: &str, account: &Account) -> bool {
+    let service_key = app.service_sk.verifying_key();
+    account.valid_keys().contains(&service_key)
+        || app.db.get_pending_keys(user_id.to_string()).contains(&service_key.to_string())
+}
+
+// Build the transaction adding the service key to an account
+async fn build_recovery_enable(
+    app: &Arc<AppState>,
+    user_id: &str,
+) -> anyhow::Result<(Account, UnsignedTransaction)> {
+    let Some(account) = fetch_prover_account(app, user_id).await?.account else {
+        return Err(StatusError::not_found(format!("Account {} not found", user_id)).into());
+    };
+    if has_service_key(app, user_id, &account) {
+        let message = format!("Recovery is already enabled for account {}", user_id);
+        return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+    }
+    let unsigned_tx = app
+        .prover
+        .clone()
+        .build_request()
+        .to_modify_account(&account)
+        .add_key(app.service_sk.verifying_key())?
+        .transaction();
+    Ok((account, unsigned_tx))
+}
+
+// Request signing payload to add the service key to an account
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn request_recovery_enable(
+    app: Arc<AppState>,
+    user_id: String,
+) -> anyhow::Result<Vec<u8>> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let (_, unsigned_tx) = build_recovery_enable(&app, &user_id).await?;
+    Ok(unsigned_tx.signing_payload()?)
+}
+
+// Send the transaction adding the service key to an account, signed by one of
+// the account's valid on-chain keys. Prism only accepts account changes signed
+// by a key of the account, so this is what lets the service add the key of an
+// executed recovery on-chain once the account lost its own keys.
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn send_recovery_enable(
+    app: Arc<AppState>,
+    user_id: String,
+    signature_bundle: SignatureBundle,
+) -> anyhow::Result<ServiceKeyGrant> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let (account, unsigned_tx) = build_recovery_enable(&app, &user_id).await?;
+    if !is_valid_account_key(&app, &user_id, &account, &signature_bundle.verifying_key) {
+        return Err(StatusError::new(
+            StatusCode::FORBIDDEN,
+            format!("Key is not a valid key of account {}", &user_id),
+        )
+        .into());
+    }
+    let payload = unsigned_tx.signing_payload()?;
+    signature_bundle
+        .verifying_key
+        .verify_signature(&payload, &signature_bundle.signature)
+        .map_err(|e| StatusError::bad_request(format!("Invalid signature: {}", e)))?;
+
+    ensure_pending_capacity(app.clone()).await?;
+    let signing_key = signature_bundle.verifying_key.clone();
+    let tx_id = transaction_id(&payload, &signature_bundle);
+    let tx = unsigned_tx.externally_signed(signature_bundle);
+    tracing::info!("Submitting transaction to add the service key to account {}", &user_id);
+    let span = info_span!("prover.queue_update", account_id = %user_id, tx_id = %tx_id);
+    app.prover.clone().validate_and_queue_update(tx).instrument(span).await?;
+
+    let service_key = app.service_sk.verifying_key().to_string();
+    store_pending_key(&app, &user_id, &service_key, &tx_id, &signing_key.to_string());
+    record_key_use(&app, &user_id, &signing_key);
+    Ok(ServiceKeyGrant { service_key, tx_id })
+}
+
+// Record a key queued to be added on-chain by a transaction signed by the
+// given key
+fn store_pending_key(app: &AppState, user_id: &str, key: &str, tx_id: &str, signing_key: &str) {
+    app.db.insert_pending_key(user_id.to_string(), key.to_string());
+    app.db.update_key_metadata(user_id.to_string(), key.to_string(), |metadata| {
+        metadata.created_at = Some(now_secs());
+        metadata.added_by = Some(signing_key.to_string());
+    });
+    app.db.append_event(
+        user_id.to_string(),
+        AccountEvent::new(EventKind::KeyAdded)
+            .with_tx_id(tx_id.to_string())
+            .with_signing_key(signing_key.to_string())
+            .with_key(key.to_string()),
+    );
+}
+
+// Queue the transaction adding a recovered key on-chain, signed with the
+// service key the account added when enabling recovery. Returns its id.
+async fn submit_recovered_key(
+    app: &Arc<AppState>,
+    user_id: &str,
+    account: &Account,
+    new_key: VerifyingKey,
+) -> anyhow::Result<String> {
+    let unsigned_tx = app
+        .prover
+        .clone()
+        .build_request()
+        .to_modify_account(account)
+        .add_key(new_key)?
+        .transaction();
+    let payload = unsigned_tx.signing_payload()?;
+    let signature = app.service_sk.sign(&payload)?;
+    let signature_bundle = SignatureBundle::new(app.service_sk.verifying_key(), signature);
+
+    ensure_pending_capacity(app.clone()).await?;
+    let tx_id = transaction_id(&payload, &signature_bundle);
+    let tx = unsigned_tx.externally_signed(signature_bundle);
+    tracing::info!("Submitting transaction to add recovered key to account {}", user_id);
+    let span = info_span!("prover.queue_update", account_id = %user_id, tx_id = %tx_id);
+    app.prover.clone().validate_and_queue_update(tx).instrument(span).await?;
+    Ok(tx_id)
+}
+
+// Add the key of an approved recovery to the account once its time lock
+// expired. The service signs the transaction adding it on-chain with its own
+// key, which the account has to have added beforehand, see
+// `send_recovery_enable`.
+#[tracing::instrument(skip_all, fields(account_id = %user_id))]
+pub async fn execute_recovery(
+    app: Arc<AppState>,
+    user_id: String,
+    recovery_id: String,
+) -> anyhow::Result<RecoveryRequest> {
+    let user_id = lookup_account_id(&app, &user_id);
+    let recovery = find_pending_recovery(&app, &user_id, &recovery_id)?;
+    let new_key = parse_cosmos_adr36_verifying_key(recovery.new_key.clone())
+        .map_err(|e| e.context(StatusError::bad_request("Invalid recovery key")))?;
+    let Some(account) = fetch_prover_account(&app, &user_id).await?.account else {
+        return Err(StatusError::not_found(format!("Account {} not found", &user_id)).into());
+    };
+    if !account.valid_keys().contains(&app.service_sk.verifying_key()) {
+        let message = "The service key is not on-chain for the account yet";
+        return Err(StatusError::new(StatusCode::CONFLICT, message).into());
+    }
+
+    // Mark the recovery as executed first so it cannot be executed twice
+    app.db
+        .update_recovery(user_id.clone(), &recovery_id, |recovery| {
+            ensure_recovery_pending(recovery)?;
+            let now = now_secs();
+            if !recovery.is_executable(now) {
+                let message = "Recovery is not approved or its time lock has not expired yet";
+                return Err(StatusError::new(StatusCode::FORBIDDEN, message).into());
+            }
+            recovery.status = RecoveryStatus::Executed;
+            recovery.updated_at = now;
+            Ok(())
+        })
+        .ok_or_else(|| StatusError::not_found(format!("Recovery {} not found", recovery_id)))??;
+
+    let tx_id = match submit_recovered_key(&app, &user_id, &account, new_key).await {
+        Ok(tx_id) => tx_id,
+        Err(e) => {
+            app.db.update_recovery(user_id.clone(), &recovery_id, |recovery| {
+                recovery.status = RecoveryStatus::Pending;
+                Ok(())
+            });
+            return Err(e);
+        }
+    };
+    let recovery = app
+        .db
+        .update_recovery(user_id.clone(), &recovery_id, |recovery| {
+            recovery.tx_id = Some(tx_id.clone());
+            Ok(())
+        })
+        .ok_or_else(|| StatusError::not_found(format!("Recovery {} not found", recovery_id)))??;
+    let service_key = app.service_sk.verifying_key().to_string();
+    store_pending_key(&app, &user_id, &recovery.new_key, &tx_id, &service_key);
+    app.db.append_event(
+        user_id.clone(),
+        AccountEvent::new(EventKind::RecoveryExecuted)
+            .with_tx_id(tx_id)
+            .with_key(recovery.new_key.clone()),
+    );
+    tracing::info!("Executed recovery {} of account {}", &recovery_id, &user_id);
+    Ok(recovery)
+}
+
+// A page of the event log of an account
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryPage {
+    pub events: Vec<AccountEvent>,
+    // Cursor to pass to get the next page, if there are more events
+    pub next_cursor: Option<u64>,
+    // Encoding of the transaction hashes and keys of the events
+    #[serde(default)]
+    pub encoding: BinaryEncoding,
+}
+
+// Page through the event log of an account, starting at the event with the
+// given sequence number
+pub fn get_history(
+    app: Arc<AppState>,
+    user_id: String,
+    cursor: Option<u64>,
+    limit: Option<usize>,
+) -> HistoryPage {
+    let user_id = lookup_account_id(&app, &user_id);
+    let limit = limit.unwrap_or(DEFAULT_HISTORY_PAGE_SIZE).clamp(1, MAX_HISTORY_PAGE_SIZE);
+    let (events, has_more) = app.db.get_events(user_id, cursor.unwrap_or_default(), limit);
+    let next_cursor = has_more.then(|| events.last().map(|event| event.seq + 1)).flatten();
+    HistoryPage { events, next_cursor, encoding: BinaryEncoding::default() }
+}
+
+// Find an off-chain data entry that can still be changed
+fn find_changeable_data(
+    app: Arc<AppState>,
+    user_id: &str,
+    entry_id: &str,
+) -> anyhow::Result<DataRecord> {
+    if entry_id.starts_with(ONCHAIN_ID_PREFIX) {
+        return Err(StatusError::bad_request("On-chain data entries cannot be changed").into());
+    }
+    app.db
+        .get_data(user_id.to_string())
+        .into_iter()
+        .find(|record| record.id == entry_id && !record.is_deleted())
+        .ok_or_else(|| StatusError::not_found(format!("Data entry {} not found", entry_id)).into())
+}
+
+// Register a JSON schema that data entries can be validated against, returning
+// its version. A registered schema is only replaced when the next version is
+// given explicitly.
+pub fn register_schema(
+    app: Arc<AppState>,
+    schema_id: String,
+    version: Option<u64>,
+    schema: Value,
+) -> anyhow::Result<u64> {
+    check_schema(&schema)?;
+    let version = version.unwrap_or(1);
+    app.db.insert_schema(schema_id.clone(), version, schema).map_err(|current| {
+        let message = match current {
+            0 => format!("Schema {} does not exist, register it without a version", schema_id),
+            current => format!("Schema {} already exists at version {}", schema_id, current),
+        };
+        StatusError::new(StatusCode::CONFLICT, message)
+    })?;
+    Ok(version)
+}
+
+// Check that an entry of the given size fits in the data quota of an account
+pub fn check_data_quota(
+    limits: &LimitsConfig,
+    existing: &[&DataRecord],
+    data_len: usize,
+) -> anyhow::Result<()> {
+    if existing.len() >= limits.max_data_items_per_account {
+        return Err(StatusError::new(
+            StatusCode::FORBIDDEN,
+            format!("Account already holds {} data entries", limits.max_data_items_per_account),
+        )
+        .into());
+    }
+    let used: usize = existing.iter().map(|record| record.value.len()).sum();
+    if used + data_len > limits.max_data_bytes_per_account {
+        return Err(StatusError::new(
+            StatusCode::FORBIDDEN,
+            format!("Account data would exceed {} bytes", limits.max_data_bytes_per_account),
+        )
+        .into());
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use prism_client::SigningKey;
 
+    use super::*;
+
     #[test]
-    fn test_print_info() {
-        let service_signing_key = SigningKey::new_ed25519();
-
-        let username = "test_user".to_string();
-        let user_identity_signing_key = SigningKey::new_cosmos_adr36();
-        let user_identity_verifying_key = user_identity_signing_key.verifying_key();
-
-        println!("username: {:?}", username);
-        println!("user_identity_verifying_key: {:?}", user_identity_verifying_key.to_string());
-        println!(
-            "service_signing_verifying_key: {:?}",
-            service_signing_key.verifying_key().to_string()
+    fn test_merge_sources_orders_and_dedups() {
+        let values = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+        let merged = merge_sources(
+            values(&["a", "b"]),
+            values(&["c", "a"]),
+            values(&["d", "c"]),
+            |value: &String| value.as_str(),
         );
+        let expected = [
+            ("a", Source::OnChain),
+            ("b", Source::OnChain),
+            ("c", Source::OffChainPending),
+            ("d", Source::OffChainOnly),
+        ];
+        assert_eq!(merged.len(), expected.len());
+        for ((entry, entry_source), (value, source)) in merged.iter().zip(expected) {
+            assert_eq!(entry, value);
+            assert_eq!(*entry_source, source);
+        }
+    }
+
+    #[test]
+    fn test_check_data_quota() {
+        let limits = LimitsConfig {
+            max_data_items_per_account: 2,
+            max_data_bytes_per_account: 10,
+            ..Default::default()
+        };
+        let (abcd, a, b) = (
+            DataRecord::raw(0, "abcd".to_string()),
+            DataRecord::raw(0, "a".to_string()),
+            DataRecord::raw(1, "b".to_string()),
+        );
+        assert!(check_data_quota(&limits, &[&abcd], 6).is_ok());
+        assert!(check_data_quota(&limits, &[&abcd], 7).is_err());
+        assert!(check_data_quota(&limits, &[&a, &b], 1).is_err());
+    }
+
+    #[test]
+    fn test_verifying_key_round_trip() {
+        let user_key = SigningKey::new_cosmos_adr36().verifying_key();
+        let parsed = parse_cosmos_adr36_verifying_key(user_key.to_string()).unwrap();
+        assert_eq!(parsed, user_key);
     }
 }
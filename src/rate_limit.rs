@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, to_bytes};
+use axum::extract::{ConnectInfo, MatchedPath, NestedPath, Query, Request, State};
+use axum::http::StatusCode;
+use axum::http::header::RETRY_AFTER;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::app::AppState;
+use crate::config::BucketConfig;
+use crate::id_policy::normalize_case;
+
+// Number of buckets above which idle buckets are pruned
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Clone)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(config: &BucketConfig, now: Instant) -> Self {
+        Self { tokens: config.capacity as f64, last_refill: now }
+    }
+
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        let refilled = self.tokens + elapsed * config.refill_per_second;
+        self.tokens = refilled.min(config.capacity as f64);
+        self.last_refill = now;
+    }
+
+    // Take a token from the bucket, or return how long to wait until one is
+    // available
+    fn try_take(&mut self, config: &BucketConfig, now: Instant) -> Result<(), Duration> {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        if config.refill_per_second <= 0.0 {
+            return Err(Duration::MAX);
+        }
+        let wait = (1.0 - self.tokens) / config.refill_per_second;
+        Err(Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX))
+    }
+}
+
+// Token bucket rate limiter keyed by an arbitrary scope string
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, (TokenBucket, BucketConfig)>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Take a token from the bucket of the given scope, or return how long the
+    // caller should wait before retrying
+    pub fn check(
+        &self,
+        scope: String,
+        config: &BucketConfig,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            // Full buckets behave exactly like fresh ones, so they can be dropped
+            buckets.retain(|_, (bucket, config)| {
+                bucket.refill(config, now);
+                bucket.tokens < config.capacity as f64
+            });
+        }
+        let (bucket, bucket_config) =
+            buckets.entry(scope).or_insert_with(|| (TokenBucket::new(config, now), *config));
+        *bucket_config = *config;
+        bucket.try_take(config, now)
+    }
+
+    // Check that the buckets hold a token for every charge of the given
+    // requests, in order. Returns the index of the first request that cannot
+    // be paid for and how long to wait. Tokens are only taken if `commit` is
+    // set and every request can be paid for.
+    pub fn check_all(
+        &self,
+        requests: &[Vec<(String, BucketConfig)>],
+        commit: bool,
+        now: Instant,
+    ) -> Result<(), (usize, Duration)> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut charged: HashMap<&str, (TokenBucket, BucketConfig)> = HashMap::new();
+        for (index, charges) in requests.iter().enumerate() {
+            for (scope, config) in charges {
+                let (bucket, _) = charged.entry(scope.as_str()).or_insert_with(|| {
+                    let bucket = buckets.get(scope).map(|(bucket, _)| bucket.clone());
+                    (bucket.unwrap_or_else(|| TokenBucket::new(config, now)), *config)
+                });
+                bucket.try_take(config, now).map_err(|wait| (index, wait))?;
+            }
+        }
+        if commit {
+            let charged = charged.into_iter().map(|(scope, entry)| (scope.to_string(), entry));
+            buckets.extend(charged);
+        }
+        Ok(())
+    }
+}
+
+// Buckets of a route a request is charged against: the per-IP bucket of the
+// given client IP and the per-account bucket of the given account id,
+// whichever are configured
+pub fn route_buckets(
+    state: &AppState,
+    route: &str,
+    ip: Option<IpAddr>,
+    account_id: Option<&str>,
+) -> Vec<(String, BucketConfig)> {
+    let config = &state.config.rate_limit;
+    let Some(limits) = config.routes.get(route).filter(|_| config.enabled) else {
+        return Vec::new();
+    };
+    let mut buckets = Vec::new();
+    if let (Some(per_ip), Some(ip)) = (limits.per_ip, ip) {
+        buckets.push((format!("ip:{}:{}", ip, route), per_ip));
+    }
+    if let (Some(per_account), Some(account_id)) = (limits.per_account, account_id) {
+        // Ids differing only in case share the bucket of the account they find
+        let account_id = normalize_case(&state.config.account_id, account_id);
+        buckets.push((format!("account:{}:{}", account_id, route), per_account));
+    }
+    buckets
+}
+
+// Take a token from the buckets of a route for the given client IP and
+// account id. Returns how long the caller should wait when one is exhausted.
+pub fn check_route(
+    state: &AppState,
+    route: &str,
+    ip: Option<IpAddr>,
+    account_id: Option<&str>,
+) -> Result<(), Duration> {
+    let now = Instant::now();
+    for (scope, config) in route_buckets(state, route, ip, account_id) {
+        state.rate_limiter.check(scope, &config, now)?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct AccountIdField {
+    id: String,
+}
+
+// Middleware applying the per-route rate limits of the config, keyed by client
+// IP and by the account id found in the query string or JSON body
+pub async fn rate_limit(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let config = &state.config.rate_limit;
+    let Some(matched_path) = req.extensions().get::<MatchedPath>() else {
+        return next.run(req).await;
+    };
+    // Routes are configured relative to the API router, which may be nested
+    // inside another application
+    let nested_path = req.extensions().get::<NestedPath>().map(NestedPath::as_str);
+    let route = matched_path.as_str();
+    let route = nested_path.and_then(|prefix| route.strip_prefix(prefix)).unwrap_or(route);
+    let route = route.to_string();
+    let Some(limits) = config.routes.get(&route).filter(|_| config.enabled) else {
+        return next.run(req).await;
+    };
+
+    let ip = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+    if let Err(wait) = check_route(&state, &route, ip, None) {
+        return too_many_requests(wait);
+    }
+    if limits.per_account.is_none() {
+        return next.run(req).await;
+    }
+
+    let (parts, body) = req.into_parts();
+    let bytes = match to_bytes(body, state.config.limits.max_body_bytes).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Error: Request body too large").into_response();
+        }
+    };
+    let account_id = Query::<AccountIdField>::try_from_uri(&parts.uri)
+        .map(|query| query.0.id)
+        .ok()
+        .or_else(|| serde_json::from_slice::<AccountIdField>(&bytes).ok().map(|field| field.id));
+    if let Some(account_id) = account_id {
+        if let Err(wait) = check_route(&state, &route, None, Some(&account_id)) {
+            return too_many_requests(wait);
+        }
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+// Whole seconds to wait before retrying, at least one
+pub fn retry_after_secs(wait: Duration) -> u64 {
+    (wait.as_secs_f64().ceil().min(u32::MAX as f64) as u64).max(1)
+}
+
+fn too_many_requests(wait: Duration) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, retry_after_secs(wait).to_string())],
+        "Error: Rate limit exceeded",
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_limits_and_refills() {
+        let limiter = RateLimiter::new();
+        let config = BucketConfig { capacity: 2, refill_per_second: 1.0 };
+        let start = Instant::now();
+
+        assert!(limiter.check("a".to_string(), &config, start).is_ok());
+        assert!(limiter.check("a".to_string(), &config, start).is_ok());
+        let wait = limiter.check("a".to_string(), &config, start).unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+
+        // Other scopes have their own bucket
+        assert!(limiter.check("b".to_string(), &config, start).is_ok());
+
+        // Checking requests together takes nothing until all can be paid for
+        let requests = vec![vec![("b".to_string(), config)], vec![("b".to_string(), config)]];
+        assert_eq!(limiter.check_all(&requests, true, start).unwrap_err().0, 1);
+        assert!(limiter.check_all(&requests[..1], false, start).is_ok());
+        assert!(limiter.check_all(&requests[..1], true, start).is_ok());
+        assert!(limiter.check("b".to_string(), &config, start).is_err());
+
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.check("a".to_string(), &config, later).is_ok());
+        assert!(limiter.check("a".to_string(), &config, later).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use prism_client::{Account, PrismApi as _};
+use prism_common::transaction::{Transaction, UnsignedTransaction};
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppState;
+use crate::encoding::BinaryEncoding;
+use crate::history::transaction_id;
+use crate::ops::{Source, account_view, fetch_prover_account, index_account_keys};
+use crate::utils::now_secs;
+
+// Off-chain keys and data of an account that are not on-chain. Data entries
+// are never submitted to the prover, so they are only ever off-chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountDrift {
+    pub id: String,
+    // Keys submitted to the prover but not yet included
+    pub pending_keys: Vec<String>,
+    // Keys only stored off-chain
+    pub offchain_keys: Vec<String>,
+    // Ids of data entries only stored off-chain
+    pub offchain_data: Vec<String>,
+    // Why the account could not be checked, in which case the lists are empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DriftReport {
+    // Unix timestamp in seconds
+    pub generated_at: u64,
+    // Last DA height scanned
+    pub height: u64,
+    // Accounts found on-chain that were missing from the off-chain database
+    pub discovered_accounts: Vec<String>,
+    pub accounts: Vec<AccountDrift>,
+    // Encoding of the keys of the report
+    #[serde(default)]
+    pub encoding: BinaryEncoding,
+}
+
+// Run the reconciler forever, once right away and then at the configured
+// interval
+pub async fn run_reconciler(app: Arc<AppState>) {
+    let interval = Duration::from_secs(app.config.reconciler.interval_secs.max(1));
+    loop {
+        match reconcile(app.clone()).await {
+            Ok(report) => {
+                if !report.accounts.is_empty() {
+                    tracing::warn!(
+                        "Reconciler found {} accounts with off-chain state not on-chain",
+                        report.accounts.len()
+                    );
+                }
+            }
+            Err(e) => tracing::error!("Reconciliation failed: {:?}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+// Bring the off-chain database in line with the prover. Accounts that appear
+// in DA transactions since the last run are added to the database with their
+// keys indexed, the inclusion height of transactions submitted by the service
+// is recorded on their history events, pending keys that made it on-chain are
+// cleared and the key index refreshed, and the off-chain state that never made it on-chain is
+// reported. Accounts that cannot be checked are reported with their error.
+pub async fn reconcile(app: Arc<AppState>) -> anyhow::Result<DriftReport> {
+    let latest_height = app.da.get_latest_height().await?;
+    let config_start = app.config.reconciler.start_height.saturating_sub(1);
+    let start_height = app.db.get_reconciled_height().max(config_start);
+
+    // Height each account id was first seen at in this run
+    let mut seen_ids = BTreeMap::new();
+    let mut scanned_height = start_height;
+    for height in start_height + 1..=latest_height {
+        match app.da.get_transactions(height).await {
+            Ok(transactions) => {
+                for tx in transactions {
+                    record_inclusion(&app, &tx, height);
+                    seen_ids.entry(tx.id).or_insert(height);
+                }
+            }
+            Err(e) => {
+                // Retry from this height on the next run
+                tracing::warn!("Failed to get transactions at height {}: {:?}", height, e);
+                break;
+            }
+        }
+        scanned_height = height;
+    }
+
+    let known_ids: BTreeSet<String> = app.db.get_accounts().into_iter().collect();
+    let mut discovered_accounts = Vec::new();
+    let mut resume_height = scanned_height;
+    let unknown_ids = seen_ids.iter().filter(|(id, _)| !known_ids.contains(*id));
+    for (id, first_height) in unknown_ids {
+        let account = match fetch_prover_account(&app, id).await {
+            Ok(response) => response.account,
+            Err(e) => {
+                // Scan the account's transactions again on the next run
+                tracing::warn!("Failed to get discovered account {}: {:?}", id, e);
+                resume_height = resume_height.min(first_height - 1);
+                continue;
+            }
+        };
+        if let Some(account) = account {
+            tracing::info!("Discovered account {} on-chain", id);
+            app.db.insert_account(id.clone(), account.clone());
+            index_account_keys(app.clone(), id.clone(), &account);
+            discovered_accounts.push(id.clone());
+        }
+    }
+    app.db.set_reconciled_height(resume_height);
+
+    let mut accounts = Vec::new();
+    for id in app.db.get_accounts() {
+        let view = match refresh_account(app.clone(), id.clone()).await {
+            Ok(account) => account_view(&app, id.clone(), &account),
+            Err(e) => {
+                tracing::warn!("Failed to reconcile account {}: {:?}", id, e);
+                accounts.push(AccountDrift {
+                    id,
+                    pending_keys: Vec::new(),
+                    offchain_keys: Vec::new(),
+                    offchain_data: Vec::new(),
+                    error: Some(format!("{:#}", e)),
+                });
+                continue;
+            }
+        };
+
+        let keys_from = |source: Source| -> Vec<String> {
+            let keys = view.keys.iter().filter(|key| key.source == source);
+            keys.map(|key| key.value.clone()).collect()
+        };
+        let offchain_data = view.data.iter().filter(|data| data.source == Source::OffChainOnly);
+        let drift = AccountDrift {
+            id,
+            pending_keys: keys_from(Source::OffChainPending),
+            offchain_keys: keys_from(Source::OffChainOnly),
+            offchain_data: offchain_data.map(|data| data.record.id.clone()).collect(),
+            error: None,
+        };
+        let has_drift = !(drift.pending_keys.is_empty()
+            && drift.offchain_keys.is_empty()
+            && drift.offchain_data.is_empty());
+        if has_drift {
+            accounts.push(drift);
+        }
+    }
+
+    let report = DriftReport {
+        generated_at: now_secs(),
+        height: scanned_height,
+        discovered_accounts,
+        accounts,
+        encoding: BinaryEncoding::default(),
+    };
+    app.db.set_drift_report(report.clone());
+    Ok(report)
+}
+
+// Fetch an account from the prover, clear its pending keys that made it
+// on-chain and refresh its key index
+pub async fn refresh_account(app: Arc<AppState>, id: String) -> anyhow::Result<Account> {
+    let account = fetch_prover_account(&app, &id).await?.account.unwrap_or_default();
+    let onchain_keys: Vec<String> =
+        account.valid_keys().iter().map(|key| key.to_string()).collect();
+    app.db.clear_included(id.clone(), &onchain_keys);
+    index_account_keys(app, id, &account);
+    Ok(account)
+}
+
+// Record the height a transaction was included at on the history event that
+// submitted it, matched by its service-local id
+fn record_inclusion(app: &AppState, tx: &Transaction, height: u64) {
+    let unsigned_tx =
+        UnsignedTransaction { id: tx.id.clone(), operation: tx.operation.clone(), nonce: tx.nonce };
+    match unsigned_tx.signing_payload() {
+        Ok(payload) => {
+            let tx_id = transaction_id(&payload, &tx.signature_bundle);
+            app.db.set_event_epoch(&tx.id, &tx_id, height);
+        }
+        Err(e) => tracing::warn!("Failed to encode transaction of {}: {:?}", tx.id, e),
+    }
+}
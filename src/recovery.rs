@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+
+// Guardians of an account: other accounts, `threshold` of which can together
+// authorize a new key for the account when it lost all of its keys
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecoverySetup {
+    pub guardians: Vec<String>,
+    pub threshold: usize,
+    // Incremented on every change and part of the signed payload, so an old
+    // setup cannot be replayed
+    pub revision: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryStatus {
+    Pending,
+    Executed,
+    Cancelled,
+    // Did not reach the threshold of approvals within the approval window
+    Expired,
+    // Superseded by a recovery started by a guardian before reaching the
+    // threshold
+    Replaced,
+}
+
+// Approval of a recovery by a guardian, signed by one of its account keys
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GuardianApproval {
+    pub guardian: String,
+    pub signing_key: String,
+    pub approved_at: u64,
+}
+
+// Request to add a new key to an account through its guardians
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryRequest {
+    pub id: String,
+    pub new_key: String,
+    pub status: RecoveryStatus,
+    // Guardian that started the recovery. Starting counts as its approval.
+    pub started_by: String,
+    pub approvals: Vec<GuardianApproval>,
+    pub created_at: u64,
+    // The recovery expires if it has not reached the threshold by then
+    pub expires_at: u64,
+    // Set once the threshold of approvals is reached. The key is added from
+    // then on, which leaves the account's existing keys time to cancel.
+    pub executable_at: Option<u64>,
+    // Service-local id of the transaction adding the key on-chain, set when
+    // the recovery is executed
+    pub tx_id: Option<String>,
+    pub updated_at: u64,
+}
+
+impl RecoveryRequest {
+    pub fn is_executable(&self, now: u64) -> bool {
+        self.status == RecoveryStatus::Pending
+            && self.executable_at.is_some_and(|executable_at| executable_at <= now)
+    }
+
+    // Whether the recovery is pending without having reached the threshold
+    pub fn is_unapproved(&self) -> bool {
+        self.status == RecoveryStatus::Pending && self.executable_at.is_none()
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.is_unapproved() && self.expires_at <= now
+    }
+}
+
+// Transaction adding the service key to an account. With it on-chain, the
+// service can sign the transaction adding the key of an executed recovery.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServiceKeyGrant {
+    pub service_key: String,
+    // Service-local id of the transaction adding the service key
+    pub tx_id: String,
+}
+
+// Guardians of an account and the recoveries started for it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountRecovery {
+    pub setup: Option<RecoverySetup>,
+    pub recoveries: Vec<RecoveryRequest>,
+}
+
+#[derive(Serialize)]
+struct RecoverySetupPayload<'a> {
+    service_id: &'a str,
+    account_id: &'a str,
+    guardians: &'a [String],
+    threshold: usize,
+    revision: u64,
+}
+
+// Bytes an account key has to sign to set the guardians of the account
+pub fn recovery_setup_payload(
+    service_id: &str,
+    account_id: &str,
+    guardians: &[String],
+    threshold: usize,
+    revision: u64,
+) -> Vec<u8> {
+    let payload = RecoverySetupPayload { service_id, account_id, guardians, threshold, revision };
+    serde_json::to_vec(&payload).expect("recovery setup payload is always serializable")
+}
+
+#[derive(Serialize)]
+struct RecoveryStartPayload<'a> {
+    service_id: &'a str,
+    account_id: &'a str,
+    action: &'a str,
+    new_key: &'a str,
+    revision: u64,
+    // Number of recoveries started for the account so far, which makes the
+    // signature single use
+    sequence: usize,
+}
+
+// Bytes a key of a guardian has to sign to start a recovery
+pub fn recovery_start_payload(
+    service_id: &str,
+    account_id: &str,
+    new_key: &str,
+    revision: u64,
+    sequence: usize,
+) -> Vec<u8> {
+    let payload = RecoveryStartPayload {
+        service_id,
+        account_id,
+        action: "start",
+        new_key,
+        revision,
+        sequence,
+    };
+    serde_json::to_vec(&payload).expect("recovery start payload is always serializable")
+}
+
+#[derive(Serialize)]
+struct RecoveryActionPayload<'a> {
+    service_id: &'a str,
+    account_id: &'a str,
+    recovery_id: &'a str,
+    action: &'a str,
+    new_key: &'a str,
+}
+
+// Bytes a key of a guardian has to sign to approve a recovery
+pub fn recovery_approval_payload(
+    service_id: &str,
+    account_id: &str,
+    recovery: &RecoveryRequest,
+) -> Vec<u8> {
+    recovery_action_payload(service_id, account_id, recovery, "approve")
+}
+
+// Bytes a key of the account has to sign to cancel a recovery
+pub fn recovery_cancel_payload(
+    service_id: &str,
+    account_id: &str,
+    recovery: &RecoveryRequest,
+) -> Vec<u8> {
+    recovery_action_payload(service_id, account_id, recovery, "cancel")
+}
+
+fn recovery_action_payload(
+    service_id: &str,
+    account_id: &str,
+    recovery: &RecoveryRequest,
+    action: &str,
+) -> Vec<u8> {
+    let payload = RecoveryActionPayload {
+        service_id,
+        account_id,
+        recovery_id: &recovery.id,
+        action,
+        new_key: &recovery.new_key,
+    };
+    serde_json::to_vec(&payload).expect("recovery action payload is always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_payloads_differ() {
+        let recovery = RecoveryRequest {
+            id: "recovery".to_string(),
+            new_key: "key".to_string(),
+            status: RecoveryStatus::Pending,
+            started_by: "bob".to_string(),
+            approvals: vec![],
+            created_at: 0,
+            expires_at: 5,
+            executable_at: Some(10),
+            tx_id: None,
+            updated_at: 0,
+        };
+        // An approval signature cannot be used to cancel, and the other way
+        assert_ne!(
+            recovery_approval_payload("service", "alice", &recovery),
+            recovery_cancel_payload("service", "alice", &recovery),
+        );
+        assert!(!recovery.is_executable(9));
+        assert!(recovery.is_executable(10));
+        // Approved recoveries do not expire
+        assert!(!recovery.is_expired(20));
+        let cancelled = RecoveryRequest { status: RecoveryStatus::Cancelled, ..recovery.clone() };
+        assert!(!cancelled.is_executable(10));
+        let unapproved = RecoveryRequest { executable_at: None, ..recovery };
+        assert!(!unapproved.is_expired(4));
+        assert!(unapproved.is_expired(5));
+    }
+}
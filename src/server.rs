@@ -1,107 +1,68 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
 
-use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use anyhow::Context as _;
+
+use axum::extract::{ConnectInfo, DefaultBodyLimit, MatchedPath, Path, Query, Request, State};
+use axum::http::{HeaderName, StatusCode};
 use axum::response::IntoResponse;
-use axum::routing::{get, post};
-use axum::{Json, Router};
-use prism_client::Account;
-use prism_serde::base64::ToBase64;
-use serde::{Deserialize, Serialize};
+use axum::routing::{delete, get, post};
+use axum::{Extension, Json, Router, middleware};
+use tokio::net::TcpListener;
 use tower_http::cors::{Any, CorsLayer};
-
-use crate::app::{AppError, AppState, HandlerResult};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tracing::{Level, Span, info_span};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use crate::api::{
+    AccountQuery, AccountResult, AccountsByKeyQuery, AccountsByKeyResponse, AddAccountRequest,
+    AddDataRequest, AddKeyRequest, AddSessionDataRequest, ApproveRecoveryRequest, BatchRequest,
+    CancelRecoveryRequest, DataAction, DataRecordResponse, GetDataResponse, GetKeyResponse,
+    HistoryQuery, ListAccountsResponse, ListSchemasResponse, ListSessionKeysResponse,
+    PendingRevocationsResponse, RecoveryActionRequest, RegisterSchemaRequest,
+    RequestCreateAccountRequest, RequestCreateAccountResponse, RequestDataChangeRequest,
+    RequestKeyMetadataRequest, RequestRecoverySetupRequest, RequestRevokeExpiredKeyRequest,
+    RequestSessionDataRequest, RequestSessionKeyRequest, RequestStartRecoveryRequest,
+    ReserveIdsRequest, ReservedIdsResponse, RevokeKeyRequest, RevokeSessionKeyRequest,
+    SchemaResponse, SendCreateAccountRequest, SendDataChangeRequest, SendEnableRecoveryRequest,
+    SendRecoverySetupRequest, SendRevokeExpiredKeyRequest, SendSessionKeyRequest,
+    StartRecoveryRequest, UpdateKeyMetadataRequest,
+};
+use crate::app::{AppError, AppState, HandlerResult, StatusError};
+use crate::auth::AdminAuth;
+use crate::batch::execute_batch;
 use crate::config::AppConfig;
-use crate::ops::{add_key, get_account, request_create_account, send_create_account};
-use crate::utils::{parse_cosmos_adr36_verifying_key, parse_signature_bundle, remove_duplicates};
-
-#[derive(Deserialize, Serialize, Debug)]
-struct SendCreateAccountRequest {
-    id: String,
-    // The verifying key is in base64 format
-    verifying_key: String,
-    // The signature is in base64 format
-    signature: String,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct RequestCreateAccountRequest {
-    id: String,
-    verifying_key: String,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct AddKeyRequest {
-    id: String,
-    verifying_key: String,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct AddDataRequest {
-    id: String,
-    data: String,
-}
-
-#[derive(Serialize)]
-struct AccountResult {
-    id: String,
-}
-
-#[derive(Serialize)]
-struct AccountInfo {
-    id: String,
-    nonce: u64,
-    data: Vec<String>,
-    keys: Vec<String>,
-}
-
-#[derive(Serialize)]
-struct ListAccountsResponse {
-    accounts: Vec<AccountInfo>,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct RequestCreateAccountResponse {
-    payload: Vec<u8>,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct GetDataResponse {
-    data: Vec<String>,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct ListKeysRequest {
-    id: String,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct AddAccountRequest {
-    id: String,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct GetKeyResponse {
-    key: Vec<String>,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct GetAccountQuery {
-    id: String,
-}
-
-// Run the server with the given app state and config
-// Panics if the server fails to start
-pub async fn run_server(app_state: Arc<AppState>, config: AppConfig) {
-    // Wrap app_state in Arc
-    let app_state = app_state.clone();
-
+use crate::content::content_negotiation;
+use crate::data::{DataChange, NewData};
+use crate::encoding::BinaryEncoding;
+use crate::idempotency::idempotency;
+use crate::key_metadata::KeyMetadataUpdate;
+use crate::ops::{
+    add_account, add_data, add_key, add_session_data, approve_recovery, build_account_view,
+    cancel_recovery, execute_recovery, find_accounts_by_key, get_history, get_recovery,
+    list_account_views, list_pending_revocations, list_session_keys, register_schema,
+    register_service, release_account_id, request_create_account, request_data_change,
+    request_key_metadata_update, request_recovery_approval, request_recovery_cancel,
+    request_recovery_enable, request_recovery_setup, request_recovery_start,
+    request_revoke_expired_key, request_session_data, request_session_key, reserve_account_ids,
+    revoke_key, revoke_session_key, send_create_account, send_data_change, send_recovery_enable,
+    send_recovery_setup, send_revoke_expired_key, send_session_key, start_recovery,
+    update_key_metadata,
+};
+use crate::rate_limit::rate_limit;
+use crate::telemetry::remote_context;
+use crate::utils::{parse_cosmos_adr36_verifying_key, parse_signature_bundle};
+
+// Build the router serving the API with the given app state, to be served
+// with `serve` or mounted inside another application
+pub fn build_router(app_state: Arc<AppState>, config: &AppConfig) -> Router {
     let cors = CorsLayer::new().allow_methods(Any).allow_origin(Any).allow_headers(Any);
 
-    // Build the router
-    let app = Router::new()
+    Router::new()
         .route("/v1/health", get(health_check_handler))
+        .route("/v1/limits", get(limits_handler))
+        .route("/v1/batch", post(batch_handler))
         .route("/v1/account/get", get(get_account_handler))
         .route("/v1/account/add-manual", post(add_account_handler))
         .route("/v1/account/get-key", get(get_key_handler))
@@ -109,17 +70,110 @@ pub async fn run_server(app_state: Arc<AppState>, config: AppConfig) {
         .route("/v1/account/send-create", post(send_create_account_handler))
         .route("/v1/account/request-create", post(request_create_account_handler))
         .route("/v1/account/add-key", post(add_key_handler))
+        .route("/v1/account/revoke-key", post(revoke_key_handler))
+        .route("/v1/account/request-key-metadata", post(request_key_metadata_handler))
+        .route("/v1/account/send-key-metadata", post(update_key_metadata_handler))
+        .route("/v1/account/pending-revocations", get(list_pending_revocations_handler))
+        .route("/v1/account/request-revoke-expired", post(request_revoke_expired_key_handler))
+        .route("/v1/account/send-revoke-expired", post(send_revoke_expired_key_handler))
         .route("/v1/account/add-data", post(add_data_handler))
+        .route("/v1/account/request-change-data", post(request_data_change_handler))
+        .route("/v1/account/send-change-data", post(send_data_change_handler))
+        .route("/v1/account/sessions", get(list_session_keys_handler))
+        .route("/v1/account/session/request-create", post(request_session_key_handler))
+        .route("/v1/account/session/send-create", post(send_session_key_handler))
+        .route("/v1/account/session/revoke", post(revoke_session_key_handler))
+        .route("/v1/account/session/request-add-data", post(request_session_data_handler))
+        .route("/v1/account/session/add-data", post(add_session_data_handler))
+        .route("/v1/account/recovery", get(get_recovery_handler))
+        .route("/v1/account/recovery/request-enable", post(request_recovery_enable_handler))
+        .route("/v1/account/recovery/send-enable", post(send_recovery_enable_handler))
+        .route("/v1/account/recovery/request-setup", post(request_recovery_setup_handler))
+        .route("/v1/account/recovery/send-setup", post(send_recovery_setup_handler))
+        .route("/v1/account/recovery/request-start", post(request_recovery_start_handler))
+        .route("/v1/account/recovery/start", post(start_recovery_handler))
+        .route("/v1/account/recovery/request-approve", post(request_recovery_approval_handler))
+        .route("/v1/account/recovery/approve", post(approve_recovery_handler))
+        .route("/v1/account/recovery/request-cancel", post(request_recovery_cancel_handler))
+        .route("/v1/account/recovery/cancel", post(cancel_recovery_handler))
+        .route("/v1/account/recovery/execute", post(execute_recovery_handler))
         .route("/v1/account/list-accounts", get(list_accounts_handler))
-        .route("/v1/account/list-keys", get(list_keys_handler))
+        .route("/v1/account/list-keys/{id}", get(list_keys_handler))
+        .route("/v1/account/by-key", get(accounts_by_key_handler))
+        .route("/v1/account/history", get(history_handler))
+        .route("/v1/schemas", get(list_schemas_handler).post(register_schema_handler))
+        .route("/v1/schemas/{id}", get(get_schema_handler))
+        .route("/v1/admin/reserved-ids", get(list_reserved_ids_handler).post(reserve_ids_handler))
+        .route("/v1/admin/reserved-ids/{id}", delete(release_id_handler))
+        .route("/v1/admin/drift", get(drift_report_handler))
+        .route("/v1/admin/register-service", post(register_service_handler))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), rate_limit))
+        .layer(middleware::from_fn_with_state(app_state.clone(), idempotency))
+        .layer(middleware::from_fn_with_state(app_state.clone(), content_negotiation))
+        .layer(DefaultBodyLimit::max(config.limits.max_body_bytes))
         .with_state(app_state)
-        .layer(cors);
+        .layer(cors)
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Span of a request, named after its route and carrying the id set from or
+// added to its `X-Request-Id` header so every log line of the request can be
+// correlated. The span continues the trace of the caller when the request has
+// a `traceparent` header.
+fn request_span(req: &Request) -> Span {
+    let request_id = req.headers().get(REQUEST_ID_HEADER).and_then(|id| id.to_str().ok());
+    let route = req.extensions().get::<MatchedPath>().map(MatchedPath::as_str);
+    let route = route.unwrap_or(req.uri().path());
+    let span = info_span!(
+        "request",
+        otel.name = %format!("{} {}", req.method(), route),
+        otel.kind = "server",
+        method = %req.method(),
+        path = %req.uri().path(),
+        http.route = route,
+        request_id = request_id.unwrap_or_default(),
+    );
+    span.set_parent(remote_context(req.headers()));
+    span
+}
+
+// Serve a router on a bound listener until the shutdown signal completes.
+// Client addresses are made available to the router for per-IP rate limits.
+pub async fn serve<F>(listener: TcpListener, router: Router, shutdown: F) -> anyhow::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+// Run the server with the given app state and config on the configured port,
+// until Ctrl-C is received
+pub async fn run_server(app_state: Arc<AppState>, config: AppConfig) -> anyhow::Result<()> {
+    let router = build_router(app_state, &config);
 
-    // Run the server
     let addr = SocketAddr::from(([0, 0, 0, 0], config.server.port));
+    let listener =
+        TcpListener::bind(addr).await.with_context(|| format!("Failed to bind {}", addr))?;
     tracing::info!("Server running on {}", addr);
 
-    axum::serve(tokio::net::TcpListener::bind(addr).await.unwrap(), app).await.unwrap();
+    serve(listener, router, async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {:?}", e);
+            std::future::pending::<()>().await;
+        }
+    })
+    .await
 }
 
 // Handlers
@@ -129,18 +183,25 @@ async fn health_check_handler() -> impl IntoResponse {
     (StatusCode::OK, "OK")
 }
 
+// Size limits enforced on requests and account data
+async fn limits_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (StatusCode::OK, Json(state.config.limits.clone()))
+}
+
 async fn request_create_account_handler(
     State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
     Json(req): Json<RequestCreateAccountRequest>,
 ) -> HandlerResult<impl IntoResponse> {
     let state = state.clone();
     let verifying_key = parse_cosmos_adr36_verifying_key(req.verifying_key)
-        .map_err(|e| AppError(anyhow::anyhow!("Invalid verifying key: {}", e)))?;
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid verifying key"))))?;
     let bytes_to_be_signed = request_create_account(state, req.id, verifying_key)
         .await
-        .map_err(|e| AppError(anyhow::anyhow!("Failed to request account creation: {}", e)))?;
+        .map_err(|e| AppError(e.context("Failed to request account creation")))?;
 
-    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload: bytes_to_be_signed })))
+    let payload = encoding.encode(&bytes_to_be_signed);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
 }
 
 async fn send_create_account_handler(
@@ -149,10 +210,10 @@ async fn send_create_account_handler(
 ) -> HandlerResult<impl IntoResponse> {
     let state = state.clone();
     let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
-        .map_err(|e| AppError(anyhow::anyhow!("Invalid signature bundle: {}", e)))?;
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
     let account = send_create_account(state, req.id, signature_bundle)
         .await
-        .map_err(|e| AppError(anyhow::anyhow!("Failed to send account creation: {}", e)))?;
+        .map_err(|e| AppError(e.context("Failed to send account creation")))?;
 
     Ok((StatusCode::OK, Json(AccountResult { id: account.id().to_string() })))
 }
@@ -163,39 +224,130 @@ async fn add_key_handler(
 ) -> HandlerResult<impl IntoResponse> {
     let state = state.clone();
     let new_key = parse_cosmos_adr36_verifying_key(req.verifying_key)
-        .map_err(|e| AppError(anyhow::anyhow!("Invalid verifying key: {}", e)))?;
-    let account = add_key(state, req.id, new_key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid verifying key"))))?;
+    let account = add_key(state, req.id, new_key, req.expires_at)
         .await
-        .map_err(|e| AppError(anyhow::anyhow!("Failed to add key: {}", e)))?;
+        .map_err(|e| AppError(e.context("Failed to add key")))?;
 
     Ok((StatusCode::OK, Json(AccountResult { id: account.id().to_string() })))
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-struct GetDataQuery {
-    id: String,
+async fn revoke_key_handler(
+    _admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RevokeKeyRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let key = parse_cosmos_adr36_verifying_key(req.verifying_key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid verifying key"))))?;
+    revoke_key(state, req.id.clone(), key)
+        .await
+        .map_err(|e| AppError(e.context("Failed to revoke key")))?;
+
+    Ok((StatusCode::OK, Json(AccountResult { id: req.id })))
+}
+
+async fn list_pending_revocations_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Query(query): Query<AccountQuery>,
+) -> HandlerResult<impl IntoResponse> {
+    let revocations = list_pending_revocations(state.clone(), query.id);
+    let revocations = revocations.into_iter().map(|r| encoding.encode_revocation(r));
+    let revocations = revocations.collect::<anyhow::Result<_>>()?;
+    Ok((StatusCode::OK, Json(PendingRevocationsResponse { revocations, encoding })))
+}
+
+async fn request_revoke_expired_key_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RequestRevokeExpiredKeyRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let key = parse_cosmos_adr36_verifying_key(req.key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid key"))))?;
+    let payload = request_revoke_expired_key(state, req.id, key)
+        .await
+        .map_err(|e| AppError(e.context("Failed to request expired key revocation")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
+
+async fn send_revoke_expired_key_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<SendRevokeExpiredKeyRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let key = parse_cosmos_adr36_verifying_key(req.key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid key"))))?;
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let revocation = send_revoke_expired_key(state, req.id, key, signature_bundle)
+        .await
+        .map_err(|e| AppError(e.context("Failed to revoke expired key")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_revocation(revocation)?)))
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-struct GetKeyQuery {
-    id: String,
+async fn request_key_metadata_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RequestKeyMetadataRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let key = parse_cosmos_adr36_verifying_key(req.key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid key"))))?;
+    let update = KeyMetadataUpdate { label: req.label, wallet_type: req.wallet_type };
+    let payload = request_key_metadata_update(state, req.id, key, update)
+        .await
+        .map_err(|e| AppError(e.context("Failed to request key metadata update")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
 }
+
+async fn update_key_metadata_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<UpdateKeyMetadataRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let key = parse_cosmos_adr36_verifying_key(req.key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid key"))))?;
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let update = KeyMetadataUpdate { label: req.label, wallet_type: req.wallet_type };
+    let metadata = update_key_metadata(state, req.id, key, update, signature_bundle)
+        .await
+        .map_err(|e| AppError(e.context("Failed to update key metadata")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_key_metadata(metadata)?)))
+}
+
 async fn get_data_handler(
     State(state): State<Arc<AppState>>,
-    Query(query): Query<GetDataQuery>,
+    encoding: BinaryEncoding,
+    Query(query): Query<AccountQuery>,
 ) -> HandlerResult<impl IntoResponse> {
     let state = state.clone();
-    let data = state.db.clone().get_data(query.id);
-    Ok((StatusCode::OK, Json(GetDataResponse { data })))
+    let view = build_account_view(state, query.id)
+        .await
+        .map_err(|e| AppError(e.context("Failed to get data")))?;
+    Ok((StatusCode::OK, Json(GetDataResponse { data: encoding.encode_data(view.data)?, encoding })))
 }
 
 async fn get_key_handler(
     State(state): State<Arc<AppState>>,
-    Query(query): Query<GetKeyQuery>,
+    encoding: BinaryEncoding,
+    Query(query): Query<AccountQuery>,
 ) -> HandlerResult<impl IntoResponse> {
     let state = state.clone();
-    let key = state.db.clone().get_key(query.id);
-    Ok((StatusCode::OK, Json(GetKeyResponse { key })))
+    let view = build_account_view(state, query.id)
+        .await
+        .map_err(|e| AppError(e.context("Failed to get keys")))?;
+    Ok((StatusCode::OK, Json(GetKeyResponse { key: encoding.encode_keys(view.keys)?, encoding })))
 }
 
 async fn add_data_handler(
@@ -203,97 +355,488 @@ async fn add_data_handler(
     Json(req): Json<AddDataRequest>,
 ) -> HandlerResult<impl IntoResponse> {
     let state = state.clone();
-    state.db.clone().insert_data(req.id.clone(), req.data.clone());
-    Ok((StatusCode::OK, Json(AccountResult { id: req.id })))
+    let data = NewData::with_defaults(req.data, req.type_tag, req.content_type, req.schema);
+    let account = add_data(state, req.id, data)
+        .await
+        .map_err(|e| AppError(e.context("Failed to add data")))?;
+    Ok((StatusCode::OK, Json(AccountResult { id: account.id().to_string() })))
 }
 
-async fn get_account_handler(
+fn data_change(
+    action: DataAction,
+    data: Option<String>,
+    type_tag: Option<String>,
+    content_type: Option<String>,
+    schema: Option<String>,
+) -> Result<DataChange, AppError> {
+    match action {
+        DataAction::Delete => Ok(DataChange::Delete),
+        DataAction::Replace => {
+            let value = data.ok_or_else(|| {
+                AppError(StatusError::bad_request("Replacing a data entry requires data").into())
+            })?;
+            Ok(DataChange::Replace(NewData::with_defaults(value, type_tag, content_type, schema)))
+        }
+    }
+}
+
+async fn request_data_change_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RequestDataChangeRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let change = data_change(req.action, req.data, req.type_tag, req.content_type, req.schema)?;
+    let payload = request_data_change(state, req.id, req.entry_id, change)
+        .await
+        .map_err(|e| AppError(e.context("Failed to request data change")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
+
+async fn send_data_change_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<SendDataChangeRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let change = data_change(req.action, req.data, req.type_tag, req.content_type, req.schema)?;
+    let record = send_data_change(state, req.id, req.entry_id, change, signature_bundle)
+        .await
+        .map_err(|e| AppError(e.context("Failed to change data")))?;
+
+    let record = encoding.encode_record(record)?;
+    Ok((StatusCode::OK, Json(DataRecordResponse { record, encoding })))
+}
+
+async fn request_session_key_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RequestSessionKeyRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let session_key = parse_cosmos_adr36_verifying_key(req.session_key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid session key"))))?;
+    let payload = request_session_key(state, req.id, session_key, req.permissions, req.expires_at)
+        .await
+        .map_err(|e| AppError(e.context("Failed to request session key")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
+
+async fn send_session_key_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<SendSessionKeyRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let session_key = parse_cosmos_adr36_verifying_key(req.session_key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid session key"))))?;
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let session = send_session_key(
+        state,
+        req.id,
+        session_key,
+        req.permissions,
+        req.expires_at,
+        signature_bundle,
+    )
+    .await
+    .map_err(|e| AppError(e.context("Failed to authorize session key")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_session(session)?)))
+}
+
+async fn list_session_keys_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Query(query): Query<AccountQuery>,
+) -> HandlerResult<impl IntoResponse> {
+    let sessions = encoding.encode_sessions(list_session_keys(state.clone(), query.id))?;
+    Ok((StatusCode::OK, Json(ListSessionKeysResponse { sessions, encoding })))
+}
+
+async fn revoke_session_key_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RevokeSessionKeyRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let session_key = parse_cosmos_adr36_verifying_key(req.session_key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid session key"))))?;
+    let session = revoke_session_key(state, req.id, session_key)
+        .map_err(|e| AppError(e.context("Failed to revoke session key")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_session(session)?)))
+}
+
+async fn request_session_data_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RequestSessionDataRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let data = NewData::with_defaults(req.data, req.type_tag, req.content_type, req.schema);
+    let payload = request_session_data(state, req.id, data, req.nonce)
+        .await
+        .map_err(|e| AppError(e.context("Failed to request session data")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
+
+async fn add_session_data_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<AddSessionDataRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let signature_bundle = parse_signature_bundle(req.session_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let data = NewData::with_defaults(req.data, req.type_tag, req.content_type, req.schema);
+    let record = add_session_data(state, req.id, data, req.nonce, signature_bundle)
+        .await
+        .map_err(|e| AppError(e.context("Failed to add data with session key")))?;
+
+    let record = encoding.encode_record(record)?;
+    Ok((StatusCode::OK, Json(DataRecordResponse { record, encoding })))
+}
+
+async fn get_recovery_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Query(query): Query<AccountQuery>,
+) -> HandlerResult<impl IntoResponse> {
+    let recovery = get_recovery(state.clone(), query.id);
+    Ok((StatusCode::OK, Json(encoding.encode_account_recovery(recovery)?)))
+}
+
+async fn request_recovery_enable_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<AccountQuery>,
+) -> HandlerResult<impl IntoResponse> {
+    let payload = request_recovery_enable(state.clone(), req.id)
+        .await
+        .map_err(|e| AppError(e.context("Failed to request recovery enable transaction")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
+
+async fn send_recovery_enable_handler(
     State(state): State<Arc<AppState>>,
-    Query(query): Query<GetAccountQuery>,
+    encoding: BinaryEncoding,
+    Json(req): Json<SendEnableRecoveryRequest>,
 ) -> HandlerResult<impl IntoResponse> {
-    tracing::info!("Getting account for {}", query.id);
     let state = state.clone();
-    let account = get_account(state.clone(), query.id.clone())
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let grant = send_recovery_enable(state, req.id, signature_bundle)
         .await
-        .map_err(|e| AppError(anyhow::anyhow!("Failed to get account: {}", e)))?;
+        .map_err(|e| AppError(e.context("Failed to enable recovery")))?;
 
-    let account = account.account.unwrap_or_default();
+    Ok((StatusCode::OK, Json(encoding.encode_service_key_grant(grant)?)))
+}
 
-    let onchain_data: Vec<String> =
-        account.clone().signed_data().iter().map(|data| data.data.to_base64()).collect();
+async fn request_recovery_setup_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RequestRecoverySetupRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let payload = request_recovery_setup(state, req.id, req.guardians, req.threshold)
+        .await
+        .map_err(|e| AppError(e.context("Failed to request recovery setup")))?;
 
-    let off_chain_data: Vec<String> = state.db.clone().get_data(query.id.clone());
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
 
-    let data: Vec<String> = onchain_data.iter().chain(off_chain_data.iter()).cloned().collect();
+async fn send_recovery_setup_handler(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SendRecoverySetupRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let setup = send_recovery_setup(state, req.id, req.guardians, req.threshold, signature_bundle)
+        .await
+        .map_err(|e| AppError(e.context("Failed to set up recovery")))?;
 
-    let info = AccountInfo {
-        id: account.id().to_string(),
-        nonce: account.nonce(),
-        keys: account.valid_keys().iter().map(|key| key.to_string()).collect(),
-        data: remove_duplicates(data),
-    };
+    Ok((StatusCode::OK, Json(setup)))
+}
 
-    Ok((StatusCode::OK, Json(info)))
+async fn request_recovery_start_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RequestStartRecoveryRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let new_key = parse_cosmos_adr36_verifying_key(req.new_key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid verifying key"))))?;
+    let payload = request_recovery_start(state, req.id, new_key)
+        .await
+        .map_err(|e| AppError(e.context("Failed to request recovery start")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
+
+async fn start_recovery_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<StartRecoveryRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let new_key = parse_cosmos_adr36_verifying_key(req.new_key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid verifying key"))))?;
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let recovery = start_recovery(state, req.id, new_key, req.guardian, signature_bundle)
+        .await
+        .map_err(|e| AppError(e.context("Failed to start recovery")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_recovery(recovery)?)))
+}
+
+async fn request_recovery_approval_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RecoveryActionRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let payload = request_recovery_approval(state.clone(), req.id, req.recovery_id)
+        .map_err(|e| AppError(e.context("Failed to request recovery approval")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
+
+async fn approve_recovery_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<ApproveRecoveryRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let recovery = approve_recovery(state, req.id, req.recovery_id, req.guardian, signature_bundle)
+        .await
+        .map_err(|e| AppError(e.context("Failed to approve recovery")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_recovery(recovery)?)))
+}
+
+async fn request_recovery_cancel_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RecoveryActionRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let payload = request_recovery_cancel(state.clone(), req.id, req.recovery_id)
+        .map_err(|e| AppError(e.context("Failed to request recovery cancellation")))?;
+
+    let payload = encoding.encode(&payload);
+    Ok((StatusCode::OK, Json(RequestCreateAccountResponse { payload, encoding })))
+}
+
+async fn cancel_recovery_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<CancelRecoveryRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let signature_bundle = parse_signature_bundle(req.verifying_key, req.signature)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid signature bundle"))))?;
+    let recovery = cancel_recovery(state, req.id, req.recovery_id, signature_bundle)
+        .await
+        .map_err(|e| AppError(e.context("Failed to cancel recovery")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_recovery(recovery)?)))
+}
+
+async fn execute_recovery_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Json(req): Json<RecoveryActionRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let recovery = execute_recovery(state.clone(), req.id, req.recovery_id)
+        .await
+        .map_err(|e| AppError(e.context("Failed to execute recovery")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_recovery(recovery)?)))
+}
+
+async fn get_account_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Query(query): Query<AccountQuery>,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let view = build_account_view(state, query.id)
+        .await
+        .map_err(|e| AppError(e.context("Failed to get account")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_view(view)?)))
 }
 
 async fn add_account_handler(
+    _admin: AdminAuth,
     State(state): State<Arc<AppState>>,
     Json(req): Json<AddAccountRequest>,
 ) -> HandlerResult<impl IntoResponse> {
     let state = state.clone();
-    state.db.clone().insert_account(req.id.clone(), Account::default());
-    Ok((StatusCode::OK, Json(AccountResult { id: req.id })))
+    let id = add_account(state, req.id)
+        .await
+        .map_err(|e| AppError(e.context("Failed to add account")))?;
+    Ok((StatusCode::OK, Json(AccountResult { id })))
 }
 
 async fn list_accounts_handler(
     State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
 ) -> HandlerResult<impl IntoResponse> {
-    let state = state.clone();
-    let accounts = state.db.clone().get_accounts();
-
-    let mut accounts_info = Vec::new();
-    for id in accounts {
-        match get_account(state.clone(), id.clone()).await {
-            Ok(account) => {
-                let account = account.account.unwrap_or_default();
-                let onchain_keys: Vec<String> =
-                    account.clone().valid_keys().iter().map(|key| key.to_string()).collect();
-                let onchain_data: Vec<String> = account
-                    .clone()
-                    .signed_data()
-                    .iter()
-                    .map(|data| data.data.to_base64())
-                    .collect();
-                let offchain_data: Vec<String> = state.db.clone().get_data(id.clone());
-                let offchain_keys: Vec<String> = state.db.clone().get_keys(id.clone());
-
-                let keys: Vec<String> =
-                    onchain_keys.iter().chain(offchain_keys.iter()).cloned().collect();
-                let data: Vec<String> =
-                    onchain_data.iter().chain(offchain_data.iter()).cloned().collect();
-                accounts_info.push(AccountInfo {
-                    id: id.clone(),
-                    keys: remove_duplicates(keys),
-                    data: remove_duplicates(data),
-                    nonce: account.clone().nonce(),
-                });
-            }
-            Err(e) => {
-                tracing::warn!("Failed to get account for {}: {}", id, e);
-                continue; // Skip this account and continue with the next
-            }
-        }
-    }
-
-    Ok((StatusCode::OK, Json(ListAccountsResponse { accounts: accounts_info })))
+    let accounts = list_account_views(state.clone()).await;
+    let accounts = accounts.into_iter().map(|view| encoding.encode_view(view));
+    let accounts = accounts.collect::<anyhow::Result<_>>()?;
+    Ok((StatusCode::OK, Json(ListAccountsResponse { accounts })))
 }
 
 async fn list_keys_handler(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    encoding: BinaryEncoding,
+) -> HandlerResult<impl IntoResponse> {
+    let state = state.clone();
+    let view = build_account_view(state, id)
+        .await
+        .map_err(|e| AppError(e.context("Failed to list keys")))?;
+
+    Ok((StatusCode::OK, Json(encoding.encode_keys(view.keys)?)))
+}
+
+async fn batch_handler(
+    State(state): State<Arc<AppState>>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    Json(req): Json<BatchRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let client_ip = connect_info.map(|Extension(ConnectInfo(addr))| addr.ip());
+    let result = execute_batch(state.clone(), req.operations, req.atomic, client_ip)
+        .await
+        .map_err(|e| AppError(e.context("Failed to execute batch")))?;
+    Ok((StatusCode::OK, Json(result)))
+}
+
+async fn history_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Query(query): Query<HistoryQuery>,
+) -> HandlerResult<impl IntoResponse> {
+    let page = get_history(state.clone(), query.id, query.cursor, query.limit);
+    Ok((StatusCode::OK, Json(encoding.encode_history(page)?)))
+}
+
+async fn accounts_by_key_handler(
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+    Query(query): Query<AccountsByKeyQuery>,
 ) -> HandlerResult<impl IntoResponse> {
     let state = state.clone();
-    let keys = state.db.clone().get_keys(id);
+    let verifying_key = parse_cosmos_adr36_verifying_key(query.verifying_key)
+        .map_err(|e| AppError(e.context(StatusError::bad_request("Invalid verifying key"))))?;
+    let accounts = find_accounts_by_key(state, verifying_key.clone())
+        .await
+        .map_err(|e| AppError(e.context("Failed to find accounts")))?;
+
+    let verifying_key = encoding.reencode(&verifying_key.to_string())?;
+    Ok((StatusCode::OK, Json(AccountsByKeyResponse { verifying_key, accounts, encoding })))
+}
+
+async fn list_reserved_ids_handler(
+    _admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+) -> HandlerResult<impl IntoResponse> {
+    let reserved = state.db.clone().get_reserved_ids();
+    Ok((StatusCode::OK, Json(ReservedIdsResponse { reserved })))
+}
+
+async fn reserve_ids_handler(
+    _admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ReserveIdsRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let reserved = reserve_account_ids(state.clone(), req.ids);
+    Ok((StatusCode::OK, Json(ReservedIdsResponse { reserved })))
+}
+
+async fn release_id_handler(
+    _admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> HandlerResult<impl IntoResponse> {
+    let reserved = release_account_id(state.clone(), id)
+        .map_err(|e| AppError(e.context("Failed to release reserved id")))?;
+    Ok((StatusCode::OK, Json(ReservedIdsResponse { reserved })))
+}
+
+async fn register_schema_handler(
+    _admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RegisterSchemaRequest>,
+) -> HandlerResult<impl IntoResponse> {
+    let version = register_schema(state.clone(), req.id.clone(), req.version, req.schema.clone())
+        .map_err(|e| AppError(e.context("Failed to register schema")))?;
+    Ok((StatusCode::OK, Json(SchemaResponse { id: req.id, version, schema: req.schema })))
+}
+
+async fn list_schemas_handler(
+    State(state): State<Arc<AppState>>,
+) -> HandlerResult<impl IntoResponse> {
+    let schemas = state
+        .db
+        .clone()
+        .get_schemas()
+        .into_iter()
+        .map(|(id, version, schema)| SchemaResponse { id, version, schema })
+        .collect();
+    Ok((StatusCode::OK, Json(ListSchemasResponse { schemas })))
+}
 
-    Ok((StatusCode::OK, Json(keys)))
+async fn get_schema_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> HandlerResult<impl IntoResponse> {
+    let (version, schema) = state.db.clone().get_schema(id.clone()).ok_or_else(|| {
+        AppError(StatusError::not_found(format!("Schema {} not found", id)).into())
+    })?;
+    Ok((StatusCode::OK, Json(SchemaResponse { id, version, schema })))
+}
+
+// Result of the last reconciliation between the off-chain database and the
+// prover
+async fn drift_report_handler(
+    _admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+    encoding: BinaryEncoding,
+) -> HandlerResult<impl IntoResponse> {
+    let report =
+        state.db.clone().get_drift_report().ok_or_else(|| {
+            AppError(StatusError::not_found("No reconciliation has run yet").into())
+        })?;
+    Ok((StatusCode::OK, Json(encoding.encode_report(report)?)))
+}
+
+// Register the service account with the prover, if it is not registered yet
+async fn register_service_handler(
+    _admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+) -> HandlerResult<impl IntoResponse> {
+    register_service(state.clone())
+        .await
+        .map_err(|e| AppError(e.context("Failed to register service")))?;
+    Ok((StatusCode::OK, Json(AccountResult { id: state.service_id.clone() })))
 }
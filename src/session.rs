@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::NewData;
+
+// Operations a session key can be allowed to perform
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOperation {
+    AddData,
+    ChangeData,
+}
+
+// Operation granted to a session key, optionally restricted to data types
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionPermission {
+    pub operation: SessionOperation,
+    // Data types the operation is allowed on, any type when empty
+    #[serde(default)]
+    pub data_types: Vec<String>,
+}
+
+impl SessionPermission {
+    pub fn allows(&self, operation: SessionOperation, type_tag: &str) -> bool {
+        self.operation == operation
+            && (self.data_types.is_empty() || self.data_types.iter().any(|t| t == type_tag))
+    }
+}
+
+// Short-lived key an account key authorized to perform some operations on the
+// account's behalf, so users do not have to sign every request with their
+// wallet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionKey {
+    pub verifying_key: String,
+    pub permissions: Vec<SessionPermission>,
+    // Unix timestamp in seconds from which the key is no longer accepted
+    pub expires_at: u64,
+    // Account key that signed the authorization
+    pub authorized_by: String,
+    pub created_at: u64,
+    // Highest nonce used to add data with the key. Nonces must increase so a
+    // signed request cannot be replayed.
+    pub last_nonce: u64,
+    pub revoked_at: Option<u64>,
+}
+
+impl SessionKey {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.revoked_at.is_none() && now < self.expires_at
+    }
+
+    pub fn allows(&self, operation: SessionOperation, type_tag: &str) -> bool {
+        self.permissions.iter().any(|permission| permission.allows(operation, type_tag))
+    }
+}
+
+#[derive(Serialize)]
+struct SessionGrantPayload<'a> {
+    service_id: &'a str,
+    account_id: &'a str,
+    session_key: &'a str,
+    permissions: &'a [SessionPermission],
+    expires_at: u64,
+}
+
+// Bytes an account key has to sign to authorize a session key
+pub fn session_grant_payload(
+    service_id: &str,
+    account_id: &str,
+    session_key: &str,
+    permissions: &[SessionPermission],
+    expires_at: u64,
+) -> Vec<u8> {
+    let payload =
+        SessionGrantPayload { service_id, account_id, session_key, permissions, expires_at };
+    serde_json::to_vec(&payload).expect("session grant payload is always serializable")
+}
+
+#[derive(Serialize)]
+struct SessionDataPayload<'a> {
+    service_id: &'a str,
+    account_id: &'a str,
+    nonce: u64,
+    #[serde(flatten)]
+    data: &'a NewData,
+}
+
+// Bytes a session key has to sign to add a data entry to its account
+pub fn session_data_payload(
+    service_id: &str,
+    account_id: &str,
+    nonce: u64,
+    data: &NewData,
+) -> Vec<u8> {
+    let payload = SessionDataPayload { service_id, account_id, nonce, data };
+    serde_json::to_vec(&payload).expect("session data payload is always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_scope() {
+        let session = SessionKey {
+            verifying_key: "key".to_string(),
+            permissions: vec![SessionPermission {
+                operation: SessionOperation::AddData,
+                data_types: vec!["profile.bio".to_string()],
+            }],
+            expires_at: 100,
+            authorized_by: "account-key".to_string(),
+            created_at: 0,
+            last_nonce: 0,
+            revoked_at: None,
+        };
+        assert!(session.allows(SessionOperation::AddData, "profile.bio"));
+        assert!(!session.allows(SessionOperation::AddData, "profile.name"));
+        assert!(!session.allows(SessionOperation::ChangeData, "profile.bio"));
+        assert!(session.is_active(99));
+        assert!(!session.is_active(100));
+        let revoked = SessionKey { revoked_at: Some(10), ..session };
+        assert!(!revoked.is_active(50));
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use prism_client::{Signature, SignatureBundle, VerifyingKey};
 use prism_keys::CryptoAlgorithm;
@@ -26,9 +27,16 @@ pub fn parse_cosmos_adr36_verifying_key(verifying_key: String) -> anyhow::Result
     VerifyingKey::from_algorithm_and_bytes(CryptoAlgorithm::CosmosAdr36, &verifying_key_bytes)
 }
 
+// Remove duplicated entries, keeping the first occurrence of each so the
+// original ordering is preserved
 pub fn remove_duplicates(vec: Vec<String>) -> Vec<String> {
-    let set: HashSet<String> = vec.into_iter().collect();
-    set.into_iter().collect()
+    let mut seen = HashSet::new();
+    vec.into_iter().filter(|item| seen.insert(item.clone())).collect()
+}
+
+// Current unix timestamp in seconds
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
 }
 
 #[cfg(test)]
@@ -38,6 +46,12 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_remove_duplicates_keeps_order() {
+        let items = vec!["b", "a", "b", "c", "a"].into_iter().map(String::from).collect();
+        assert_eq!(remove_duplicates(items), vec!["b", "a", "c"]);
+    }
+
     #[test]
     fn test_parse_signature_bundle_simple() {
         let message = String::from("123");
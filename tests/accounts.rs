@@ -0,0 +1,258 @@
+use prism_be::config::AppConfig;
+use prism_be::reconciler::reconcile;
+use prism_serde::base64::FromBase64;
+use reqwest::StatusCode;
+use reqwest::header::ACCEPT;
+use serde_json::{Value, json};
+
+use crate::common::{TestServer, ok_json, sign, user_key};
+
+mod common;
+
+#[tokio::test]
+async fn test_account_lifecycle() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+
+    let created = ok_json(server.create_account("alice", &key).await).await;
+    assert_eq!(created["id"], "alice");
+    server.wait_for_inclusion("alice").await;
+
+    // The reconciler clears the pending key and records the height the
+    // creation was included at
+    let report = reconcile(server.state.clone()).await.unwrap();
+    let mut included_at = None;
+    for height in 1..=report.height {
+        let transactions = server.state.da.get_transactions(height).await.unwrap();
+        if transactions.iter().any(|tx| tx.id == "alice") {
+            included_at = Some(height);
+            break;
+        }
+    }
+    let history = ok_json(server.get("/v1/account/history?id=alice").await).await;
+    assert_eq!(history["events"][0]["kind"], "created");
+    assert_eq!(history["events"][0]["epoch"], included_at.unwrap());
+    assert!(history["events"][0]["tx_id"].is_string());
+
+    let account = ok_json(server.get("/v1/account/get?id=alice").await).await;
+    assert_eq!(account["id"], "alice");
+    assert_eq!(account["keys"].as_array().unwrap().len(), 1);
+    assert_eq!(account["keys"][0]["value"], verifying_key);
+    assert_eq!(account["keys"][0]["source"], "on_chain");
+
+    let accounts = ok_json(server.get("/v1/account/list-accounts").await).await;
+    let accounts = accounts["accounts"].as_array().unwrap();
+    assert!(accounts.iter().any(|account| account["id"] == "alice"));
+
+    let keys = ok_json(server.get("/v1/account/list-keys/alice").await).await;
+    assert_eq!(keys.as_array().unwrap().len(), 1);
+
+    let query = [("verifying_key", verifying_key.as_str())];
+    let by_key = ok_json(server.get_query("/v1/account/by-key", &query).await).await;
+    assert_eq!(by_key["accounts"], json!(["alice"]));
+
+    // Off-chain keys can be added and revoked by the service
+    let second_key = user_key().verifying_key().to_string();
+    let body = json!({ "id": "alice", "verifying_key": second_key });
+    ok_json(server.post("/v1/account/add-key", body.clone()).await).await;
+    let keys = ok_json(server.get("/v1/account/get-key?id=alice").await).await;
+    assert_eq!(keys["key"][1]["value"], second_key);
+    assert_eq!(keys["key"][1]["source"], "off_chain_only");
+    // Adding an off-chain key takes no signature, so it does not find the account
+    let query = [("verifying_key", second_key.as_str())];
+    let by_key = ok_json(server.get_query("/v1/account/by-key", &query).await).await;
+    assert_eq!(by_key["accounts"], json!([]));
+
+    ok_json(server.admin_post("/v1/account/revoke-key", body.clone()).await).await;
+    let keys = ok_json(server.get("/v1/account/get-key?id=alice").await).await;
+    assert_eq!(keys["key"].as_array().unwrap().len(), 1);
+    let response = server.admin_post("/v1/account/revoke-key", body).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // On-chain keys cannot
+    let body = json!({ "id": "alice", "verifying_key": verifying_key });
+    let response = server.admin_post("/v1/account/revoke-key", body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let history = ok_json(server.get("/v1/account/history?id=alice&limit=2").await).await;
+    let events = history["events"].as_array().unwrap();
+    let kinds: Vec<&str> = events.iter().map(|event| event["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, vec!["created", "key_added"]);
+    assert_eq!(history["next_cursor"], 2);
+    let history = ok_json(server.get("/v1/account/history?id=alice&cursor=2").await).await;
+    assert_eq!(history["events"][0]["kind"], "key_revoked");
+    assert!(history["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn test_create_account_errors() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+
+    let body = json!({ "id": "alice", "verifying_key": "not a key" });
+    let response = server.post("/v1/account/request-create", body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Ids violating the policy, reserved ids and the service id
+    for (id, status) in [
+        ("ab", StatusCode::BAD_REQUEST),
+        ("admin", StatusCode::CONFLICT),
+        ("prism-be-id", StatusCode::CONFLICT),
+    ] {
+        let body = json!({ "id": id, "verifying_key": verifying_key });
+        let response = server.post("/v1/account/request-create", body).await;
+        assert_eq!(response.status(), status, "id {}", id);
+    }
+
+    let body = json!({ "id": "alice", "verifying_key": verifying_key, "signature": "invalid" });
+    let response = server.post("/v1/account/send-create", body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // A signature by another key is rejected
+    let body = json!({ "id": "alice", "verifying_key": verifying_key });
+    let payload = ok_json(server.post("/v1/account/request-create", body).await).await;
+    let body = json!({
+        "id": "alice",
+        "verifying_key": verifying_key,
+        "signature": sign(&user_key(), &payload["payload"]),
+    });
+    let response = server.post("/v1/account/send-create", body).await;
+    assert!(!response.status().is_success());
+
+    // Adding keys and data requires an on-chain account
+    let body = json!({ "id": "bob", "verifying_key": verifying_key });
+    assert!(!server.post("/v1/account/add-key", body).await.status().is_success());
+    let body = json!({ "id": "bob", "data": "hello" });
+    assert!(!server.post("/v1/account/add-data", body).await.status().is_success());
+}
+
+#[tokio::test]
+async fn test_manual_account() {
+    let server = TestServer::start().await;
+
+    // Adding accounts without a key takes the admin token
+    let body = json!({ "id": "carol" });
+    let response = server.post("/v1/account/add-manual", body.clone()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let added = ok_json(server.admin_post("/v1/account/add-manual", body).await).await;
+    assert_eq!(added["id"], "carol");
+    let account = ok_json(server.get("/v1/account/get?id=carol").await).await;
+    assert_eq!(account["keys"], json!([]));
+
+    let response = server.admin_post("/v1/account/add-manual", json!({ "id": "root" })).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_account_id_case() {
+    let mut config = AppConfig::default();
+    config.rate_limit.enabled = false;
+    config.reconciler.enabled = false;
+    config.account_id.lowercase = true;
+    let server = TestServer::start_with_config(config).await;
+    let key = user_key();
+
+    let created = ok_json(server.create_account("Alice", &key).await).await;
+    assert_eq!(created["id"], "alice");
+    server.wait_for_inclusion("alice").await;
+
+    // Every lookup normalizes the id the way creation did
+    for path in ["/v1/account/get?id=ALICE", "/v1/account/history?id=Alice"] {
+        ok_json(server.get(path).await).await;
+    }
+    let keys = ok_json(server.get("/v1/account/get-key?id=Alice").await).await;
+    assert_eq!(keys["key"][0]["value"], key.verifying_key().to_string());
+    let body = json!({ "id": "ALICE", "data": "hello" });
+    ok_json(server.post("/v1/account/add-data", body).await).await;
+    let data = ok_json(server.get("/v1/account/get-data?id=alice").await).await;
+    assert_eq!(data["data"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_binary_encoding() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+    let key_bytes = Vec::<u8>::from_base64(&verifying_key).unwrap();
+    ok_json(server.create_account("alice", &key).await).await;
+
+    // Binary values are base64 by default, the encoding is part of the response
+    let keys = ok_json(server.get("/v1/account/get-key?id=alice").await).await;
+    assert_eq!(keys["encoding"], "base64");
+    assert_eq!(keys["key"][0]["value"], verifying_key);
+
+    let account = ok_json(server.get("/v1/account/get?id=alice&encoding=hex").await).await;
+    assert_eq!(account["encoding"], "hex");
+    assert_eq!(account["keys"][0]["value"], hex::encode(&key_bytes));
+    let history = ok_json(server.get("/v1/account/history?id=alice&encoding=hex").await).await;
+    assert_eq!(history["events"][0]["signing_key"], hex::encode(&key_bytes));
+
+    // The encoding can also be requested with the Accept header
+    let response = reqwest::Client::new()
+        .get(server.url("/v1/account/get-key?id=alice"))
+        .header(ACCEPT, "application/json; encoding=hex")
+        .send()
+        .await
+        .unwrap();
+    let keys = ok_json(response).await;
+    assert_eq!(keys["encoding"], "hex");
+    assert_eq!(keys["key"][0]["value"], hex::encode(&key_bytes));
+
+    let body = json!({ "id": "bob", "verifying_key": verifying_key });
+    let response = server.post("/v1/account/request-create?encoding=hex", body.clone()).await;
+    let payload = ok_json(response).await;
+    assert_eq!(payload["encoding"], "hex");
+    let base64 = ok_json(server.post("/v1/account/request-create", body).await).await;
+    let bytes = Vec::<u8>::from_base64(base64["payload"].as_str().unwrap()).unwrap();
+    assert_eq!(payload["payload"], hex::encode(bytes));
+
+    let response = server.get("/v1/account/get?id=alice&encoding=base58").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_key_metadata() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+    server.create_included_account("alice", &key).await;
+    let laptop = user_key().verifying_key().to_string();
+    let body = json!({ "id": "alice", "verifying_key": laptop });
+    ok_json(server.post("/v1/account/add-key", body).await).await;
+
+    // Metadata changes are signed by an on-chain key of the account
+    let body = json!({ "id": "alice", "key": laptop, "label": "Laptop", "wallet_type": "keplr" });
+    let response = server.post("/v1/account/request-key-metadata", body.clone()).await;
+    let payload = ok_json(response).await;
+    let mut signed = body;
+    signed["verifying_key"] = Value::from(laptop.clone());
+    signed["signature"] = Value::from(sign(&key, &payload["payload"]));
+    let response = server.post("/v1/account/send-key-metadata", signed.clone()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    signed["verifying_key"] = Value::from(verifying_key.clone());
+    let response = server.post("/v1/account/send-key-metadata", signed.clone()).await;
+    let metadata = ok_json(response).await;
+    assert_eq!(metadata["label"], "Laptop");
+    assert!(metadata["created_at"].is_u64());
+    // The signature covers the revision of the metadata and cannot be replayed
+    let response = server.post("/v1/account/send-key-metadata", signed).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let keys = ok_json(server.get("/v1/account/get-key?id=alice").await).await;
+    let keys = keys["key"].as_array().unwrap();
+    // The account key signed the creation, so it has been used
+    assert!(keys[0]["metadata"]["last_used_at"].is_u64());
+    assert_eq!(keys[0]["metadata"]["added_by"], verifying_key);
+    assert_eq!(keys[1]["metadata"]["label"], "Laptop");
+    // The service added the key without a signature of the account
+    let service_key = server.state.service_sk.verifying_key().to_string();
+    assert_eq!(keys[1]["metadata"]["added_by"], service_key);
+
+    // Only keys of the account can be described
+    let stranger = user_key().verifying_key().to_string();
+    let body = json!({ "id": "alice", "key": stranger, "label": "Phone" });
+    let response = server.post("/v1/account/request-key-metadata", body).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
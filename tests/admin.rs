@@ -0,0 +1,115 @@
+use prism_be::config::AppConfig;
+use prism_be::reconciler::reconcile;
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::common::{TestServer, ok_json, user_key};
+
+mod common;
+
+#[tokio::test]
+async fn test_health_and_limits() {
+    let server = TestServer::start().await;
+
+    let response = server.get("/v1/health").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "OK");
+
+    let limits = ok_json(server.get("/v1/limits").await).await;
+    assert_eq!(limits["max_body_bytes"], server.state.config.limits.max_body_bytes);
+    assert_eq!(limits["max_batch_operations"], server.state.config.limits.max_batch_operations);
+}
+
+#[tokio::test]
+async fn test_schemas() {
+    let server = TestServer::start().await;
+
+    let schema = json!({ "type": "object", "required": ["name"] });
+    let body = json!({ "id": "profile", "schema": schema });
+    let response = server.post("/v1/schemas", body.clone()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let registered = json!({ "id": "profile", "version": 1, "schema": schema });
+    assert_eq!(ok_json(server.admin_post("/v1/schemas", body.clone()).await).await, registered);
+    assert_eq!(ok_json(server.get("/v1/schemas/profile").await).await, registered);
+    let schemas = ok_json(server.get("/v1/schemas").await).await;
+    assert_eq!(schemas["schemas"], json!([registered]));
+
+    // Registered schemas are only replaced by their next version
+    let schema = json!({ "type": "object", "required": ["name", "age"] });
+    let body = json!({ "id": "profile", "schema": schema });
+    let response = server.admin_post("/v1/schemas", body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = json!({ "id": "profile", "version": 3, "schema": schema });
+    let response = server.admin_post("/v1/schemas", body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = json!({ "id": "profile", "version": 2, "schema": schema });
+    assert_eq!(ok_json(server.admin_post("/v1/schemas", body.clone()).await).await, body);
+    assert_eq!(ok_json(server.get("/v1/schemas/profile").await).await, body);
+
+    let body = json!({ "id": "broken", "schema": { "type": "not a type" } });
+    let response = server.admin_post("/v1/schemas", body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.get("/v1/schemas/missing").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_reserved_ids() {
+    let server = TestServer::start().await;
+    let verifying_key = user_key().verifying_key().to_string();
+
+    // Admin routes need the admin token
+    let response = server.get("/v1/admin/reserved-ids").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = reqwest::Client::new().get(server.url("/v1/admin/reserved-ids"));
+    let response = request.bearer_auth("wrong").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let body = json!({ "ids": ["Support"] });
+    let reserved = ok_json(server.admin_post("/v1/admin/reserved-ids", body).await).await;
+    assert!(reserved["reserved"].as_array().unwrap().contains(&json!("support")));
+    let listed = ok_json(server.admin_get("/v1/admin/reserved-ids").await).await;
+    assert_eq!(listed, reserved);
+
+    let body = json!({ "id": "support", "verifying_key": verifying_key });
+    let response = server.post("/v1/account/request-create", body.clone()).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let released = ok_json(server.admin_delete("/v1/admin/reserved-ids/support").await).await;
+    assert!(!released["reserved"].as_array().unwrap().contains(&json!("support")));
+    ok_json(server.post("/v1/account/request-create", body).await).await;
+
+    let response = server.admin_delete("/v1/admin/reserved-ids/support").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_admin_disabled_without_token() {
+    let mut config = AppConfig::default();
+    config.reconciler.enabled = false;
+    let server = TestServer::start_with_config(config).await;
+
+    let response = server.admin_get("/v1/admin/reserved-ids").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_register_service_and_drift() {
+    let server = TestServer::start().await;
+
+    // The service is registered on startup, registering again is a no-op
+    let response = server.admin_post("/v1/admin/register-service", json!({})).await;
+    let registered = ok_json(response).await;
+    assert_eq!(registered["id"], server.state.service_id);
+
+    assert_eq!(server.admin_get("/v1/admin/drift").await.status(), StatusCode::NOT_FOUND);
+
+    ok_json(server.admin_post("/v1/account/add-manual", json!({ "id": "carol" })).await).await;
+    let key = user_key().verifying_key().to_string();
+    server.state.db.insert_key("carol".to_string(), key.clone());
+    reconcile(server.state.clone()).await.unwrap();
+
+    let report = ok_json(server.admin_get("/v1/admin/drift").await).await;
+    let accounts = report["accounts"].as_array().unwrap();
+    let carol = accounts.iter().find(|account| account["id"] == "carol").unwrap();
+    assert_eq!(carol["offchain_keys"], json!([key]));
+}
@@ -0,0 +1,181 @@
+#![allow(dead_code)]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use prism_be::app::AppState;
+use prism_be::config::AppConfig;
+use prism_be::ops::register_service;
+use prism_be::server::{build_router, serve};
+use prism_client::{PrismApi as _, SigningKey};
+use prism_da::DataAvailabilityLayer;
+use prism_da::memory::InMemoryDataAvailabilityLayer;
+use prism_keys::CryptoAlgorithm;
+use prism_prover::webserver::WebServerConfig;
+use prism_prover::{Config, Prover};
+use prism_serde::base64::{FromBase64, ToBase64};
+use prism_storage::inmemory::InMemoryDatabase;
+use reqwest::{Response, StatusCode};
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+
+// How long to wait for a transaction to be included on-chain
+const INCLUSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Admin token of servers started with the default config
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+
+// Server running in-process against an in-memory prover and DA layer
+pub struct TestServer {
+    pub state: Arc<AppState>,
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl TestServer {
+    // Start a server with the default config, rate limiting disabled and the
+    // admin routes enabled
+    pub async fn start() -> Self {
+        let mut config = AppConfig::default();
+        config.rate_limit.enabled = false;
+        config.reconciler.enabled = false;
+        config.admin.token = Some(ADMIN_TOKEN.to_string());
+        Self::start_with_config(config).await
+    }
+
+    pub async fn start_with_config(config: AppConfig) -> Self {
+        let state = start_state(&config).await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let router = build_router(state.clone(), &config);
+        tokio::spawn(serve(listener, router, std::future::pending()));
+
+        Self { state, base_url, http: reqwest::Client::new() }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    pub async fn get(&self, path: &str) -> Response {
+        self.http.get(self.url(path)).send().await.unwrap()
+    }
+
+    pub async fn get_query(&self, path: &str, query: &[(&str, &str)]) -> Response {
+        self.http.get(self.url(path)).query(query).send().await.unwrap()
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> Response {
+        self.http.post(self.url(path)).json(&body).send().await.unwrap()
+    }
+
+    pub async fn delete(&self, path: &str) -> Response {
+        self.http.delete(self.url(path)).send().await.unwrap()
+    }
+
+    pub async fn admin_get(&self, path: &str) -> Response {
+        self.http.get(self.url(path)).bearer_auth(ADMIN_TOKEN).send().await.unwrap()
+    }
+
+    pub async fn admin_post(&self, path: &str, body: Value) -> Response {
+        let request = self.http.post(self.url(path)).bearer_auth(ADMIN_TOKEN);
+        request.json(&body).send().await.unwrap()
+    }
+
+    pub async fn admin_delete(&self, path: &str) -> Response {
+        self.http.delete(self.url(path)).bearer_auth(ADMIN_TOKEN).send().await.unwrap()
+    }
+
+    // Create an account through the request/send round trip
+    pub async fn create_account(&self, id: &str, key: &SigningKey) -> Response {
+        let verifying_key = key.verifying_key().to_string();
+        let body = json!({ "id": id, "verifying_key": verifying_key });
+        let response = self.post("/v1/account/request-create", body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let payload: Value = response.json().await.unwrap();
+
+        let body = json!({
+            "id": id,
+            "verifying_key": verifying_key,
+            "signature": sign(key, &payload["payload"]),
+        });
+        self.post("/v1/account/send-create", body).await
+    }
+
+    // Wait until the prover has included the account on-chain. The prover is
+    // queried directly so pending entries are left for the reconciler to clear.
+    pub async fn wait_for_inclusion(&self, id: &str) {
+        let deadline = tokio::time::Instant::now() + INCLUSION_TIMEOUT;
+        while tokio::time::Instant::now() < deadline {
+            if self.state.prover.clone().get_account(id).await.unwrap().account.is_some() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+        panic!("account {} was not included on-chain", id);
+    }
+
+    // Wait until the prover has included a key of an account on-chain
+    pub async fn wait_for_key(&self, id: &str, key: &str) {
+        let deadline = tokio::time::Instant::now() + INCLUSION_TIMEOUT;
+        while tokio::time::Instant::now() < deadline {
+            let account = self.state.prover.clone().get_account(id).await.unwrap().account;
+            if account.is_some_and(|account| {
+                account.valid_keys().iter().any(|valid_key| valid_key.to_string() == key)
+            }) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+        panic!("key of account {} was not included on-chain", id);
+    }
+
+    // Create an account and wait until it is on-chain
+    pub async fn create_included_account(&self, id: &str, key: &SigningKey) {
+        ok_json(self.create_account(id, key).await).await;
+        self.wait_for_inclusion(id).await;
+    }
+}
+
+// Build the app state against an in-memory prover and DA layer, with the
+// prover running and the service registered
+pub async fn start_state(config: &AppConfig) -> Arc<AppState> {
+    let (da_layer, _, _) = InMemoryDataAvailabilityLayer::new(1);
+    let da_layer = Arc::new(da_layer) as Arc<dyn DataAvailabilityLayer>;
+    let service_sk = SigningKey::new_ed25519();
+    let prover_config = Config {
+        prover: true,
+        batcher: true,
+        webserver: WebServerConfig { enabled: false, host: "127.0.0.1".to_string(), port: 0 },
+        signing_key: service_sk.clone(),
+        verifying_key: service_sk.verifying_key(),
+        start_height: 1,
+    };
+    let db = InMemoryDatabase::new();
+    let prover =
+        Arc::new(Prover::new(Arc::new(Box::new(db)), da_layer.clone(), &prover_config).unwrap());
+    let state = Arc::new(AppState::new(prover.clone(), da_layer, config, service_sk));
+
+    tokio::spawn(async move { prover.run().await });
+    register_service(state.clone()).await.unwrap();
+    state
+}
+
+// Cosmos ADR-36 key, the only kind of user key the send endpoints accept
+pub fn user_key() -> SigningKey {
+    SigningKey::new_with_algorithm(CryptoAlgorithm::CosmosAdr36).unwrap()
+}
+
+// Sign a payload returned by a request endpoint, as a base64 signature
+pub fn sign(key: &SigningKey, payload: &Value) -> String {
+    let payload = Vec::<u8>::from_base64(payload.as_str().unwrap()).unwrap();
+    key.sign(&payload).unwrap().to_bytes().to_base64()
+}
+
+// Body of a successful JSON response
+pub async fn ok_json(response: Response) -> Value {
+    let status = response.status();
+    let body = response.text().await.unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    serde_json::from_str(&body).unwrap()
+}
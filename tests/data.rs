@@ -0,0 +1,119 @@
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+use crate::common::{TestServer, ok_json, sign, user_key};
+
+mod common;
+
+#[tokio::test]
+async fn test_data_lifecycle() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+    server.create_included_account("alice", &key).await;
+
+    // The raw type is reserved for on-chain data
+    let body = json!({ "id": "alice", "data": "hello", "type": "raw" });
+    let response = server.post("/v1/account/add-data", body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = json!({ "id": "alice", "data": "hello", "type": "profile.bio" });
+    ok_json(server.post("/v1/account/add-data", body).await).await;
+    let data = ok_json(server.get("/v1/account/get-data?id=alice").await).await;
+    let entry = data["data"][0].clone();
+    assert_eq!(entry["value"], "hello");
+    assert_eq!(entry["type"], "profile.bio");
+    assert_eq!(entry["content_type"], "text/plain");
+    assert_eq!(entry["revision"], 1);
+    assert_eq!(entry["source"], "off_chain_only");
+    let entry_id = entry["id"].as_str().unwrap();
+
+    // Replace the entry with a change signed by the account key
+    let change = json!({ "id": "alice", "entry_id": entry_id, "action": "replace", "data": "hi" });
+    let payload = change_payload(&server, &change).await;
+    let signed = signed_change(&change, &verifying_key, sign(&key, &payload["payload"]));
+    let record = ok_json(server.post("/v1/account/send-change-data", signed.clone()).await).await;
+    assert_eq!(record["value"], "hi");
+    assert_eq!(record["revision"], 2);
+    assert_eq!(record["encoding"], "base64");
+
+    // The signature was for the previous revision and cannot be replayed
+    let response = server.post("/v1/account/send-change-data", signed).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Changes must be signed by a key of the account
+    let other_key = user_key();
+    let payload = change_payload(&server, &change).await;
+    let other = other_key.verifying_key().to_string();
+    let signed = signed_change(&change, &other, sign(&other_key, &payload["payload"]));
+    let response = server.post("/v1/account/send-change-data", signed).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let change = json!({ "id": "alice", "entry_id": entry_id, "action": "delete" });
+    let payload = change_payload(&server, &change).await;
+    let signed = signed_change(&change, &verifying_key, sign(&key, &payload["payload"]));
+    let path = "/v1/account/send-change-data?encoding=hex";
+    let record = ok_json(server.post(path, signed).await).await;
+    assert!(record["deleted_at"].is_u64());
+    assert_eq!(record["value"], "");
+    assert_eq!(record["encoding"], "hex");
+    let data = ok_json(server.get("/v1/account/get-data?id=alice").await).await;
+    assert_eq!(data["data"], json!([]));
+
+    let history = ok_json(server.get("/v1/account/history?id=alice").await).await;
+    let events = history["events"].as_array().unwrap();
+    let kinds: Vec<&str> = events.iter().map(|event| event["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, vec!["created", "data_added", "data_updated", "data_deleted"]);
+}
+
+#[tokio::test]
+async fn test_data_errors() {
+    let server = TestServer::start().await;
+    server.create_included_account("alice", &user_key()).await;
+
+    // Unknown entries and on-chain entries cannot be changed
+    let cases = [("missing", StatusCode::NOT_FOUND), ("chain-0", StatusCode::BAD_REQUEST)];
+    for (entry_id, status) in cases {
+        let change = json!({ "id": "alice", "entry_id": entry_id, "action": "delete" });
+        let response = server.post("/v1/account/request-change-data", change).await;
+        assert_eq!(response.status(), status, "entry {}", entry_id);
+    }
+
+    // Replacing an entry requires the new data
+    let change = json!({ "id": "alice", "entry_id": "missing", "action": "replace" });
+    let response = server.post("/v1/account/request-change-data", change).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let max_item_bytes = server.state.config.limits.max_data_item_bytes;
+    let body = json!({ "id": "alice", "data": "x".repeat(max_item_bytes + 1) });
+    let response = server.post("/v1/account/add-data", body).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let schema = json!({ "type": "object", "required": ["name"] });
+    let body = json!({ "id": "profile", "schema": schema });
+    ok_json(server.admin_post("/v1/schemas", body).await).await;
+    let body = json!({ "id": "alice", "data": r#"{"age": 1}"#, "schema": "profile" });
+    let response = server.post("/v1/account/add-data", body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = json!({ "id": "alice", "data": r#"{"name": "alice"}"#, "schema": "missing" });
+    let response = server.post("/v1/account/add-data", body).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let body = json!({ "id": "alice", "data": r#"{"name": "alice"}"#, "schema": "profile" });
+    ok_json(server.post("/v1/account/add-data", body).await).await;
+    let data = ok_json(server.get("/v1/account/get-data?id=alice").await).await;
+    assert_eq!(data["data"][0]["content_type"], "application/json");
+}
+
+// Payload to sign for a data change
+async fn change_payload(server: &TestServer, change: &Value) -> Value {
+    ok_json(server.post("/v1/account/request-change-data", change.clone()).await).await
+}
+
+// Send request for a data change with the given key and signature
+fn signed_change(change: &Value, verifying_key: &str, signature: String) -> Value {
+    let mut signed = change.clone();
+    signed["verifying_key"] = json!(verifying_key);
+    signed["signature"] = json!(signature);
+    signed
+}
@@ -0,0 +1,130 @@
+use prism_be::config::{AppConfig, BucketConfig, RouteRateLimit};
+use prism_be::grpc::grpc_service;
+use prism_be::grpc::proto::prism_be_client::PrismBeClient;
+use prism_be::grpc::proto::{
+    AccountQuery, ListAccountsRequest, RequestCreateAccountRequest, SendCreateAccountRequest,
+    Source, StreamEventsRequest,
+};
+use prism_serde::base64::ToBase64;
+use tokio::net::TcpListener;
+use tokio_stream::StreamExt as _;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Server};
+use tonic::{Code, Request};
+
+use crate::common::{start_state, user_key};
+
+mod common;
+
+// Serve the gRPC service on a free local port and connect a client to it
+async fn start_grpc() -> PrismBeClient<Channel> {
+    let mut config = AppConfig::default();
+    config.reconciler.enabled = false;
+    start_grpc_with_config(config).await
+}
+
+async fn start_grpc_with_config(config: AppConfig) -> PrismBeClient<Channel> {
+    let state = start_state(&config).await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(grpc_service(state))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    PrismBeClient::connect(format!("http://{}", addr)).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_grpc_create_account() {
+    let mut client = start_grpc().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+
+    let filter = StreamEventsRequest { id: Some("alice".to_string()) };
+    let mut events = client.stream_events(filter).await.unwrap().into_inner();
+
+    let request = RequestCreateAccountRequest {
+        id: "alice".to_string(),
+        verifying_key: verifying_key.clone(),
+    };
+    let payload = client.request_create_account(request).await.unwrap().into_inner().payload;
+    let request = SendCreateAccountRequest {
+        id: "alice".to_string(),
+        verifying_key: verifying_key.clone(),
+        signature: key.sign(&payload).unwrap().to_bytes().to_base64(),
+    };
+    let created = client.send_create_account(request).await.unwrap().into_inner();
+    assert_eq!(created.id, "alice");
+
+    let event = events.next().await.unwrap().unwrap();
+    assert_eq!((event.account_id.as_str(), event.kind.as_str()), ("alice", "created"));
+    assert!(event.tx_id.is_some());
+
+    let query = AccountQuery { id: "alice".to_string() };
+    let account = client.get_account(query).await.unwrap().into_inner();
+    assert_eq!(account.keys[0].value, verifying_key);
+    assert_eq!(account.keys[0].source(), Source::OffChainPending);
+    let metadata = account.keys[0].metadata.clone().unwrap();
+    assert_eq!(metadata.added_by, Some(verifying_key));
+
+    let accounts = client.list_accounts(ListAccountsRequest {}).await.unwrap().into_inner();
+    assert!(accounts.accounts.iter().any(|account| account.id == "alice"));
+}
+
+#[tokio::test]
+async fn test_grpc_errors() {
+    let mut client = start_grpc().await;
+    let verifying_key = user_key().verifying_key().to_string();
+
+    let request =
+        RequestCreateAccountRequest { id: "alice".to_string(), verifying_key: "bad".to_string() };
+    let error = client.request_create_account(request).await.unwrap_err();
+    assert_eq!(error.code(), Code::InvalidArgument);
+
+    // Validation is shared with the HTTP API
+    let request = RequestCreateAccountRequest { id: "admin".to_string(), verifying_key };
+    let error = client.request_create_account(request).await.unwrap_err();
+    assert_eq!(error.code(), Code::AlreadyExists);
+}
+
+#[tokio::test]
+async fn test_grpc_rate_limit_and_idempotency() {
+    let mut config = AppConfig::default();
+    config.reconciler.enabled = false;
+    config.account_id.lowercase = true;
+    let bucket = BucketConfig { capacity: 1, refill_per_second: 0.001 };
+    config.rate_limit.routes.insert(
+        "/v1/account/request-create".to_string(),
+        RouteRateLimit { per_ip: None, per_account: Some(bucket) },
+    );
+    let mut client = start_grpc_with_config(config).await;
+    let verifying_key = user_key().verifying_key().to_string();
+    let request_create = |id: &str, key: Option<&str>| {
+        let message = RequestCreateAccountRequest {
+            id: id.to_string(),
+            verifying_key: verifying_key.clone(),
+        };
+        let mut request = Request::new(message);
+        if let Some(key) = key {
+            request.metadata_mut().insert("idempotency-key", key.parse().unwrap());
+        }
+        request
+    };
+
+    let first = client.request_create_account(request_create("alice", Some("a"))).await.unwrap();
+    assert!(first.metadata().get("idempotent-replayed").is_none());
+
+    // Calls share the per-account buckets of the HTTP routes
+    let error = client.request_create_account(request_create("ALICE", None)).await.unwrap_err();
+    assert_eq!(error.code(), Code::ResourceExhausted);
+    assert!(error.metadata().get("retry-after").is_some());
+
+    // A retry with the same key gets the first response back without being
+    // charged, and the key cannot be reused for another message
+    let retry = client.request_create_account(request_create("alice", Some("a"))).await.unwrap();
+    assert_eq!(retry.metadata().get("idempotent-replayed").unwrap(), "true");
+    assert_eq!(retry.into_inner().payload, first.into_inner().payload);
+    let error = client.request_create_account(request_create("bob", Some("a"))).await.unwrap_err();
+    assert_eq!(error.code(), Code::InvalidArgument);
+}
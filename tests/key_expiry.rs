@@ -0,0 +1,109 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prism_be::key_expiry::{expire_keys, expire_keys_with};
+use prism_be::ops::fetch_prover_account;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+use crate::common::{TestServer, ok_json, sign, user_key};
+
+mod common;
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+async fn key_values(server: &TestServer) -> Vec<String> {
+    let keys = ok_json(server.get("/v1/account/get-key?id=alice").await).await;
+    let keys = keys["key"].as_array().unwrap();
+    keys.iter().map(|key| key["value"].as_str().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_key_expiry() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+    server.create_included_account("alice", &key).await;
+
+    let contractor = user_key().verifying_key().to_string();
+    let body = json!({ "id": "alice", "verifying_key": contractor, "expires_at": now() - 1 });
+    let response = server.post("/v1/account/add-key", body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = json!({ "id": "alice", "verifying_key": contractor, "expires_at": now() + 1 });
+    ok_json(server.post("/v1/account/add-key", body).await).await;
+    assert!(key_values(&server).await.contains(&contractor));
+
+    // Expired keys leave the view before the scheduler runs
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(!key_values(&server).await.contains(&contractor));
+    let query = [("verifying_key", contractor.as_str())];
+    let by_key = ok_json(server.get_query("/v1/account/by-key", &query).await).await;
+    assert_eq!(by_key["accounts"], json!([]));
+
+    // The off-chain key is revoked by the service, the on-chain key waits for
+    // a transaction signed by the account
+    server.state.db.update_key_metadata("alice".to_string(), verifying_key.clone(), |metadata| {
+        metadata.expires_at = Some(now());
+    });
+    let mut expired = expire_keys(server.state.clone()).await;
+    expired.sort_by_key(|expired| expired.revoked);
+    assert_eq!(expired.len(), 2);
+    assert_eq!((expired[0].key.as_str(), expired[0].revoked), (verifying_key.as_str(), false));
+    assert_eq!((expired[1].key.as_str(), expired[1].revoked), (contractor.as_str(), true));
+    assert!(key_values(&server).await.is_empty());
+    // Queued revocations are not queued twice
+    assert!(expire_keys(server.state.clone()).await.is_empty());
+
+    let pending = ok_json(server.get("/v1/account/pending-revocations?id=alice").await).await;
+    assert_eq!(pending["revocations"][0]["key"], verifying_key);
+    assert_eq!(pending["revocations"][0]["tx_id"], Value::Null);
+
+    let body = json!({ "id": "alice", "key": verifying_key });
+    let response = server.post("/v1/account/request-revoke-expired", body.clone()).await;
+    let payload = ok_json(response).await;
+    let mut signed = body;
+    signed["verifying_key"] = Value::from(user_key().verifying_key().to_string());
+    signed["signature"] = Value::from(sign(&key, &payload["payload"]));
+    let response = server.post("/v1/account/send-revoke-expired", signed.clone()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    signed["verifying_key"] = Value::from(verifying_key.clone());
+    let revocation = ok_json(server.post("/v1/account/send-revoke-expired", signed).await).await;
+    assert!(revocation["tx_id"].is_string());
+
+    let history = ok_json(server.get("/v1/account/history?id=alice").await).await;
+    let kinds: Vec<&str> =
+        history["events"].as_array().unwrap().iter().filter_map(|e| e["kind"].as_str()).collect();
+    assert!(kinds.contains(&"key_expired"));
+    assert!(kinds.contains(&"key_revoked"));
+}
+
+#[tokio::test]
+async fn test_key_expiry_skips_failing_accounts() {
+    let server = TestServer::start().await;
+    let mut contractors = Vec::new();
+    for id in ["alice", "bob"] {
+        server.create_included_account(id, &user_key()).await;
+        let contractor = user_key().verifying_key().to_string();
+        let body = json!({ "id": id, "verifying_key": contractor, "expires_at": now() + 1 });
+        ok_json(server.post("/v1/account/add-key", body).await).await;
+        contractors.push(contractor);
+    }
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // Bob's account cannot be read, alice's key is still handled
+    let expired = expire_keys_with(server.state.clone(), |app, id| async move {
+        if id == "bob" {
+            anyhow::bail!("prover unavailable");
+        }
+        fetch_prover_account(&app, &id).await
+    })
+    .await;
+    assert_eq!(expired.len(), 1);
+    assert_eq!((expired[0].key.as_str(), expired[0].revoked), (contractors[0].as_str(), true));
+
+    // Bob's key is picked up by the next run
+    let expired = expire_keys(server.state.clone()).await;
+    assert_eq!(expired.len(), 1);
+    assert_eq!((expired[0].key.as_str(), expired[0].revoked), (contractors[1].as_str(), true));
+}
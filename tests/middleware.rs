@@ -0,0 +1,240 @@
+use prism_be::config::{AppConfig, BucketConfig, RouteRateLimit};
+use reqwest::StatusCode;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, RETRY_AFTER};
+use serde_json::{Value, json};
+
+use crate::common::{ADMIN_TOKEN, TestServer, ok_json, sign, user_key};
+
+mod common;
+
+#[tokio::test]
+async fn test_batch() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+    let body = json!({ "id": "alice", "verifying_key": verifying_key });
+    let payload = ok_json(server.post("/v1/account/request-create", body).await).await;
+
+    // Operations are validated against the accounts created earlier in the
+    // batch, and nothing is applied if one of them fails
+    let create = json!({
+        "op": "create_account",
+        "id": "alice",
+        "verifying_key": verifying_key,
+        "signature": sign(&key, &payload["payload"]),
+    });
+    let add_data = json!({ "op": "add_data", "id": "alice", "data": "hello" });
+    let add_key = json!({ "op": "add_key", "id": "bob", "verifying_key": verifying_key });
+    let body = json!({ "operations": [create.clone(), add_data.clone(), add_key], "atomic": true });
+    let result = ok_json(server.post("/v1/batch", body).await).await;
+    assert_eq!(result["applied"], false);
+    let statuses: Vec<&str> = result["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, vec!["skipped", "skipped", "failed"]);
+
+    let body = json!({ "operations": [create, add_data.clone()], "atomic": true });
+    let result = ok_json(server.post("/v1/batch", body).await).await;
+    assert_eq!(result["applied"], true);
+    assert!(result["results"][1]["data_id"].is_string());
+    let data = ok_json(server.get("/v1/account/get-data?id=alice").await).await;
+    assert_eq!(data["data"][0]["value"], "hello");
+
+    let max_operations = server.state.config.limits.max_batch_operations;
+    let operations = vec![add_data; max_operations + 1];
+    let response = server.post("/v1/batch", json!({ "operations": operations })).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_batch_rate_limit() {
+    let mut config = AppConfig::default();
+    config.reconciler.enabled = false;
+    config.admin.token = Some(ADMIN_TOKEN.to_string());
+    let bucket = BucketConfig { capacity: 2, refill_per_second: 0.001 };
+    config.rate_limit.routes.insert(
+        "/v1/account/add-data".to_string(),
+        RouteRateLimit { per_ip: None, per_account: Some(bucket) },
+    );
+    let server = TestServer::start_with_config(config).await;
+    server.create_included_account("alice", &user_key()).await;
+
+    // Batched operations share the buckets of their endpoint and account
+    let add_data = json!({ "op": "add_data", "id": "alice", "data": "hello" });
+    let operations = vec![add_data.clone(); 3];
+    let result = ok_json(server.post("/v1/batch", json!({ "operations": operations })).await).await;
+    let results = result["results"].as_array().unwrap();
+    let statuses: Vec<&str> =
+        results.iter().map(|result| result["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, vec!["ok", "ok", "failed"]);
+    assert!(results[2]["error"].as_str().unwrap().contains("Rate limit"));
+
+    let body = json!({ "id": "alice", "data": "hello" });
+    let response = server.post("/v1/account/add-data", body).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // An exhausted bucket fails the whole batch when it is atomic
+    let body = json!({ "operations": [add_data], "atomic": true });
+    let result = ok_json(server.post("/v1/batch", body).await).await;
+    assert_eq!(result["applied"], false);
+    let data = ok_json(server.get("/v1/account/get-data?id=alice").await).await;
+    assert_eq!(data["data"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_atomic_batch_rate_limit() {
+    let mut config = AppConfig::default();
+    config.reconciler.enabled = false;
+    config.account_id.lowercase = true;
+    let bucket = BucketConfig { capacity: 2, refill_per_second: 0.001 };
+    config.rate_limit.routes.insert(
+        "/v1/account/add-data".to_string(),
+        RouteRateLimit { per_ip: None, per_account: Some(bucket) },
+    );
+    let server = TestServer::start_with_config(config).await;
+    server.create_included_account("alice", &user_key()).await;
+
+    // Rejected batches take no tokens, whether they fail validation or would
+    // exceed the limits
+    let add_data = json!({ "op": "add_data", "id": "ALICE", "data": "hello" });
+    let verifying_key = user_key().verifying_key().to_string();
+    let add_key = json!({ "op": "add_key", "id": "bob", "verifying_key": verifying_key });
+    let body = json!({ "operations": [add_data.clone(), add_key], "atomic": true });
+    let result = ok_json(server.post("/v1/batch", body).await).await;
+    assert_eq!(result["applied"], false);
+    let body = json!({ "operations": vec![add_data.clone(); 3], "atomic": true });
+    let result = ok_json(server.post("/v1/batch", body).await).await;
+    let statuses: Vec<&str> = result["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, vec!["skipped", "skipped", "failed"]);
+
+    // Ids are normalized, so both entries land on alice and use up her bucket
+    let body = json!({ "operations": vec![add_data; 2], "atomic": true });
+    let result = ok_json(server.post("/v1/batch", body).await).await;
+    assert_eq!(result["applied"], true);
+    let data = ok_json(server.get("/v1/account/get-data?id=alice").await).await;
+    assert_eq!(data["data"].as_array().unwrap().len(), 2);
+    let body = json!({ "id": "alice", "data": "hello" });
+    let response = server.post("/v1/account/add-data", body).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_idempotency_key() {
+    let server = TestServer::start().await;
+    let url = server.url("/v1/account/add-manual");
+    let http = reqwest::Client::new();
+    let send = |id: &str, key: &str| {
+        let request = http.post(&url).bearer_auth(ADMIN_TOKEN).header("Idempotency-Key", key);
+        request.json(&json!({ "id": id })).send()
+    };
+
+    let first = send("carol", "key-1").await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers().get("idempotent-replayed").is_none());
+
+    let replayed = send("carol", "key-1").await.unwrap();
+    assert_eq!(replayed.status(), StatusCode::OK);
+    assert_eq!(replayed.headers()["idempotent-replayed"], "true");
+    assert_eq!(replayed.json::<serde_json::Value>().await.unwrap(), json!({ "id": "carol" }));
+
+    let mismatch = send("dave", "key-1").await.unwrap();
+    assert_eq!(mismatch.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_rate_limit() {
+    let mut config = AppConfig::default();
+    config.reconciler.enabled = false;
+    config.admin.token = Some(ADMIN_TOKEN.to_string());
+    let bucket = BucketConfig { capacity: 2, refill_per_second: 0.001 };
+    config.rate_limit.routes.insert(
+        "/v1/account/add-manual".to_string(),
+        RouteRateLimit { per_ip: None, per_account: Some(bucket) },
+    );
+    let server = TestServer::start_with_config(config).await;
+
+    for _ in 0..2 {
+        let body = json!({ "id": "carol" });
+        ok_json(server.admin_post("/v1/account/add-manual", body).await).await;
+    }
+    let response = server.admin_post("/v1/account/add-manual", json!({ "id": "carol" })).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(RETRY_AFTER));
+
+    // Buckets are per account
+    ok_json(server.admin_post("/v1/account/add-manual", json!({ "id": "dave" })).await).await;
+}
+
+#[tokio::test]
+async fn test_content_negotiation() {
+    let server = TestServer::start().await;
+    let http = reqwest::Client::new();
+
+    let mut body = Vec::new();
+    ciborium::into_writer(&json!({ "id": "carol" }), &mut body).unwrap();
+    let response = http
+        .post(server.url("/v1/account/add-manual"))
+        .bearer_auth(ADMIN_TOKEN)
+        .header(CONTENT_TYPE, "application/cbor")
+        .header(ACCEPT, "application/cbor")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/cbor");
+    let bytes = response.bytes().await.unwrap();
+    let added: Value = ciborium::from_reader(bytes.as_ref()).unwrap();
+    assert_eq!(added, json!({ "id": "carol" }));
+
+    let response = http
+        .get(server.url("/v1/account/get?id=carol"))
+        .header(ACCEPT, "application/msgpack")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/msgpack");
+    let account: Value = rmp_serde::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!(account["id"], "carol");
+
+    // JSON stays the default, and invalid bodies are rejected
+    let response = server.get("/v1/account/get?id=carol").await;
+    assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    let response = http
+        .post(server.url("/v1/account/add-manual"))
+        .bearer_auth(ADMIN_TOKEN)
+        .header(CONTENT_TYPE, "application/cbor")
+        .body(vec![0xff, 0x00])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_request_id() {
+    let server = TestServer::start().await;
+
+    // An id is generated for requests without one
+    let response = server.get("/v1/health").await;
+    let request_id = response.headers()["x-request-id"].to_str().unwrap();
+    assert!(!request_id.is_empty());
+
+    // and the id sent by the client is propagated, on errors too
+    let response = reqwest::Client::new()
+        .get(server.url("/v1/schemas/missing"))
+        .header("X-Request-Id", "client-id-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers()["x-request-id"], "client-id-1");
+}
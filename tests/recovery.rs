@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use prism_be::config::AppConfig;
+use prism_client::SigningKey;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+use crate::common::{TestServer, ok_json, sign, user_key};
+
+mod common;
+
+// Body of a request endpoint with the signature of its payload added
+async fn signed(server: &TestServer, path: &str, body: Value, key: &SigningKey) -> Value {
+    let payload = ok_json(server.post(path, body.clone()).await).await;
+    let mut signed = body;
+    signed["verifying_key"] = Value::from(key.verifying_key().to_string());
+    signed["signature"] = Value::from(sign(key, &payload["payload"]));
+    signed
+}
+
+// Body starting a recovery of alice for the given key, signed by a guardian
+async fn start(server: &TestServer, new_key: &str, guardian: &str, key: &SigningKey) -> Value {
+    let body = json!({ "id": "alice", "new_key": new_key });
+    let mut start = signed(server, "/v1/account/recovery/request-start", body, key).await;
+    start["guardian"] = Value::from(guardian);
+    start
+}
+
+async fn approve(server: &TestServer, recovery_id: &str, guardian: &str, key: &SigningKey) {
+    let body = json!({ "id": "alice", "recovery_id": recovery_id });
+    let mut approval = signed(server, "/v1/account/recovery/request-approve", body, key).await;
+    approval["guardian"] = Value::from(guardian);
+    ok_json(server.post("/v1/account/recovery/approve", approval).await).await;
+}
+
+async fn start_recovery_server(timelock_secs: u64, approval_window_secs: u64) -> TestServer {
+    let mut config = AppConfig::default();
+    config.rate_limit.enabled = false;
+    config.reconciler.enabled = false;
+    config.recovery.timelock_secs = timelock_secs;
+    config.recovery.approval_window_secs = approval_window_secs;
+    TestServer::start_with_config(config).await
+}
+
+// Create alice with bob and carol as her guardians, both needed for a recovery
+async fn setup_guardians(server: &TestServer) -> (SigningKey, SigningKey, SigningKey) {
+    let (alice, bob, carol) = (user_key(), user_key(), user_key());
+    server.create_included_account("alice", &alice).await;
+    server.create_included_account("bob", &bob).await;
+    server.create_included_account("carol", &carol).await;
+
+    // Guardians can only be set once the account added the service key
+    let setup = json!({ "id": "alice", "guardians": ["bob", "carol"], "threshold": 2 });
+    let body = signed(server, "/v1/account/recovery/request-setup", setup.clone(), &alice).await;
+    let response = server.post("/v1/account/recovery/send-setup", body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let enable = json!({ "id": "alice" });
+    let body = signed(server, "/v1/account/recovery/request-enable", enable, &alice).await;
+    let grant = ok_json(server.post("/v1/account/recovery/send-enable", body).await).await;
+    let service_key = server.state.service_sk.verifying_key().to_string();
+    assert_eq!(grant["service_key"], service_key.as_str());
+    assert!(grant["tx_id"].is_string());
+    server.wait_for_key("alice", &service_key).await;
+
+    let body = signed(server, "/v1/account/recovery/request-setup", setup, &alice).await;
+    let setup = ok_json(server.post("/v1/account/recovery/send-setup", body.clone()).await).await;
+    assert_eq!(setup["revision"], 1);
+    // The signature was for the first revision and cannot be replayed
+    let response = server.post("/v1/account/recovery/send-setup", body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    (alice, bob, carol)
+}
+
+#[tokio::test]
+async fn test_social_recovery() {
+    let server = start_recovery_server(0, 60).await;
+    let (alice, bob, carol) = setup_guardians(&server).await;
+
+    // Only guardians can start a recovery, and each signature starts one
+    let new_key = user_key();
+    let new_key_value = new_key.verifying_key().to_string();
+    let body = start(&server, &new_key_value, "alice", &alice).await;
+    let response = server.post("/v1/account/recovery/start", body).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = start(&server, &new_key_value, "bob", &bob).await;
+    let first = ok_json(server.post("/v1/account/recovery/start", body.clone()).await).await;
+    assert_eq!(first["started_by"], "bob");
+    assert_eq!(first["approvals"].as_array().unwrap().len(), 1);
+    let response = server.post("/v1/account/recovery/start", body).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Another guardian replaces a recovery that has not reached the threshold
+    let body = start(&server, &new_key_value, "carol", &carol).await;
+    let recovery = ok_json(server.post("/v1/account/recovery/start", body).await).await;
+    let recovery_id = recovery["id"].as_str().unwrap();
+    let body = json!({ "id": "alice", "recovery_id": first["id"] });
+    let response = server.post("/v1/account/recovery/request-approve", body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let body = json!({ "id": "alice", "recovery_id": recovery_id });
+    let response = server.post("/v1/account/recovery/execute", body.clone()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    approve(&server, recovery_id, "bob", &bob).await;
+    // An approved recovery can only be cancelled by the account
+    let start_body = start(&server, &new_key_value, "bob", &bob).await;
+    let response = server.post("/v1/account/recovery/start", start_body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Alice lost her key: the service adds the new one with its own key
+    let executed = ok_json(server.post("/v1/account/recovery/execute", body.clone()).await).await;
+    assert_eq!(executed["status"], "executed");
+    assert!(executed["tx_id"].is_string());
+    let response = server.post("/v1/account/recovery/execute", body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let keys = ok_json(server.get("/v1/account/get-key?id=alice").await).await;
+    let keys = keys["key"].as_array().unwrap();
+    let key = keys.iter().find(|key| key["value"] == new_key_value.as_str()).unwrap();
+    assert_ne!(key["source"], "off_chain_only");
+    let service_key = server.state.service_sk.verifying_key().to_string();
+    assert_eq!(key["metadata"]["added_by"], service_key);
+    server.wait_for_key("alice", &new_key_value).await;
+
+    // Off-chain keys cannot cancel a recovery, the recovered on-chain key can
+    let body = start(&server, &user_key().verifying_key().to_string(), "bob", &bob).await;
+    let recovery = ok_json(server.post("/v1/account/recovery/start", body).await).await;
+    let body = json!({ "id": "alice", "recovery_id": recovery["id"] });
+    let offchain_key = user_key();
+    let add_key =
+        json!({ "id": "alice", "verifying_key": offchain_key.verifying_key().to_string() });
+    ok_json(server.post("/v1/account/add-key", add_key).await).await;
+    let path = "/v1/account/recovery/request-cancel";
+    let cancel = signed(&server, path, body.clone(), &offchain_key).await;
+    let response = server.post("/v1/account/recovery/cancel", cancel).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let cancel = signed(&server, path, body.clone(), &new_key).await;
+    let cancelled = ok_json(server.post("/v1/account/recovery/cancel", cancel).await).await;
+    assert_eq!(cancelled["status"], "cancelled");
+    let response = server.post("/v1/account/recovery/execute", body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let recovery = ok_json(server.get("/v1/account/recovery?id=alice").await).await;
+    assert_eq!(recovery["setup"]["guardians"], json!(["bob", "carol"]));
+    let statuses: Vec<&str> = recovery["recoveries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|recovery| recovery["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, ["replaced", "executed", "cancelled"]);
+}
+
+#[tokio::test]
+async fn test_recovery_expiry() {
+    let server = start_recovery_server(0, 1).await;
+    let (alice, bob, carol) = setup_guardians(&server).await;
+
+    let new_key = user_key().verifying_key().to_string();
+    let body = start(&server, &new_key, "bob", &bob).await;
+    let recovery = ok_json(server.post("/v1/account/recovery/start", body).await).await;
+    let recovery_id = recovery["id"].as_str().unwrap();
+
+    // Unapproved recoveries expire and stop blocking changes to the guardians
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let body = json!({ "id": "alice", "recovery_id": recovery_id });
+    let response = server.post("/v1/account/recovery/request-approve", body).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let recovery = ok_json(server.get("/v1/account/recovery?id=alice").await).await;
+    assert_eq!(recovery["recoveries"][0]["status"], "expired");
+
+    let setup = json!({ "id": "alice", "guardians": ["carol"], "threshold": 1 });
+    let body = signed(&server, "/v1/account/recovery/request-setup", setup, &alice).await;
+    let setup = ok_json(server.post("/v1/account/recovery/send-setup", body).await).await;
+    assert_eq!(setup["revision"], 2);
+
+    // With a threshold of one, starting a recovery approves it
+    let body = start(&server, &new_key, "carol", &carol).await;
+    let recovery = ok_json(server.post("/v1/account/recovery/start", body).await).await;
+    assert!(recovery["executable_at"].is_u64());
+}
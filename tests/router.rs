@@ -0,0 +1,78 @@
+use std::net::SocketAddr;
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use prism_be::config::AppConfig;
+use prism_be::server::build_router;
+use serde_json::{Value, json};
+use tower::ServiceExt as _;
+
+use crate::common::{ADMIN_TOKEN, start_state};
+
+mod common;
+
+#[tokio::test]
+async fn test_router_without_listener() {
+    let config = AppConfig::default();
+    let state = start_state(&config).await;
+    let router = build_router(state, &config);
+
+    let request = Request::get("/v1/health").body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Rate limited routes work without a client address
+    let body = json!({ "id": "alice", "verifying_key": "not a key" });
+    let request = Request::post("/v1/account/request-create")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_router_nested_in_another_application() {
+    let config = AppConfig::default();
+    let state = start_state(&config).await;
+    let app = Router::new()
+        .route("/status", axum::routing::get(|| async { "up" }))
+        .nest("/prism", build_router(state, &config));
+
+    let request = Request::get("/prism/v1/limits").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let limits: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(limits["max_body_bytes"], config.limits.max_body_bytes);
+
+    let request = Request::get("/status").body(Body::empty()).unwrap();
+    assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_idempotency_key_across_client_addresses() {
+    let mut config = AppConfig::default();
+    config.rate_limit.enabled = false;
+    config.admin.token = Some(ADMIN_TOKEN.to_string());
+    let state = start_state(&config).await;
+    let router = build_router(state, &config);
+
+    // A client retrying from another network gets the stored response back
+    // instead of adding the account twice
+    for (ip, replayed) in [("10.0.0.1", false), ("10.0.0.2", true)] {
+        let address: SocketAddr = format!("{}:4000", ip).parse().unwrap();
+        let mut request = Request::post("/v1/account/add-manual")
+            .header("content-type", "application/json")
+            .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+            .header("idempotency-key", "key-1")
+            .body(Body::from(json!({ "id": "carol" }).to_string()))
+            .unwrap();
+        request.extensions_mut().insert(ConnectInfo(address));
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().contains_key("idempotent-replayed"), replayed);
+    }
+}
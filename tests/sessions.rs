@@ -0,0 +1,123 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use prism_client::SigningKey;
+use reqwest::{Response, StatusCode};
+use serde_json::{Value, json};
+
+use crate::common::{TestServer, ok_json, sign, user_key};
+
+mod common;
+
+fn expires_in(secs: u64) -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + secs
+}
+
+// Add a data entry signed by a session key, asking for hex encoded values
+async fn add_session_data(
+    server: &TestServer,
+    session: &SigningKey,
+    nonce: u64,
+    type_tag: &str,
+) -> Response {
+    let body = json!({ "id": "alice", "nonce": nonce, "data": "hello", "type": type_tag });
+    let response = server.post("/v1/account/session/request-add-data", body.clone()).await;
+    let payload = ok_json(response).await;
+    let mut signed = body;
+    signed["session_key"] = Value::from(session.verifying_key().to_string());
+    signed["signature"] = Value::from(sign(session, &payload["payload"]));
+    server.post("/v1/account/session/add-data?encoding=hex", signed).await
+}
+
+#[tokio::test]
+async fn test_session_keys() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    server.create_included_account("alice", &key).await;
+
+    // Authorize a session key to add profile.bio entries for an hour
+    let session = user_key();
+    let grant = json!({
+        "id": "alice",
+        "session_key": session.verifying_key().to_string(),
+        "permissions": [{ "operation": "add_data", "data_types": ["profile.bio"] }],
+        "expires_at": expires_in(3600),
+    });
+    let response = server.post("/v1/account/session/request-create", grant.clone()).await;
+    let payload = ok_json(response).await;
+    let mut signed = grant.clone();
+    signed["verifying_key"] = Value::from(key.verifying_key().to_string());
+    signed["signature"] = Value::from(sign(&key, &payload["payload"]));
+    let response = server.post("/v1/account/session/send-create", signed.clone()).await;
+    let created = ok_json(response).await;
+    assert_eq!(created["authorized_by"], key.verifying_key().to_string());
+    let response = server.post("/v1/account/session/send-create", signed).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Grants longer than the configured maximum are rejected
+    let mut too_long = grant.clone();
+    too_long["expires_at"] = Value::from(expires_in(365 * 24 * 3600));
+    let response = server.post("/v1/account/session/request-create", too_long).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let record = ok_json(add_session_data(&server, &session, 1, "profile.bio").await).await;
+    assert_eq!(record["type"], "profile.bio");
+    // Typed values are returned as stored, in a response reporting the encoding
+    assert_eq!(record["value"], "hello");
+    assert_eq!(record["encoding"], "hex");
+
+    // Nonces cannot be replayed and the scope is enforced
+    let response = add_session_data(&server, &session, 1, "profile.bio").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = add_session_data(&server, &session, 2, "profile.name").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Keys never authorized by the account are refused
+    let response = add_session_data(&server, &user_key(), 1, "profile.bio").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let revoke = json!({ "id": "alice", "session_key": session.verifying_key().to_string() });
+    let revoked = ok_json(server.post("/v1/account/session/revoke", revoke).await).await;
+    assert!(revoked["revoked_at"].is_u64());
+    let response = add_session_data(&server, &session, 3, "profile.bio").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let sessions = ok_json(server.get("/v1/account/sessions?id=alice").await).await;
+    assert_eq!(sessions["sessions"].as_array().unwrap().len(), 1);
+    assert_eq!(sessions["sessions"][0]["last_nonce"], 1);
+    let history = ok_json(server.get("/v1/account/history?id=alice").await).await;
+    let kinds: Vec<&str> =
+        history["events"].as_array().unwrap().iter().filter_map(|e| e["kind"].as_str()).collect();
+    assert!(kinds.contains(&"session_created"));
+    assert!(kinds.contains(&"session_revoked"));
+}
+
+#[tokio::test]
+async fn test_session_lapses_with_its_authorizing_key() {
+    let server = TestServer::start().await;
+    let key = user_key();
+    let verifying_key = key.verifying_key().to_string();
+    server.create_included_account("alice", &key).await;
+
+    let session = user_key();
+    let grant = json!({
+        "id": "alice",
+        "session_key": session.verifying_key().to_string(),
+        "permissions": [{ "operation": "add_data", "data_types": [] }],
+        "expires_at": expires_in(3600),
+    });
+    let response = server.post("/v1/account/session/request-create", grant.clone()).await;
+    let payload = ok_json(response).await;
+    let mut signed = grant;
+    signed["verifying_key"] = Value::from(verifying_key.clone());
+    signed["signature"] = Value::from(sign(&key, &payload["payload"]));
+    ok_json(server.post("/v1/account/session/send-create", signed).await).await;
+    ok_json(add_session_data(&server, &session, 1, "profile.bio").await).await;
+
+    // The session is refused once the key that authorized it has expired,
+    // although the session itself has not
+    server.state.db.update_key_metadata("alice".to_string(), verifying_key, |metadata| {
+        metadata.expires_at = Some(expires_in(0));
+    });
+    let response = add_session_data(&server, &session, 2, "profile.bio").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,70 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::routing::post;
+use prism_be::config::AppConfig;
+use prism_be::logging::init_logging;
+use reqwest::StatusCode;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::common::{ADMIN_TOKEN, TestServer, ok_json};
+
+mod common;
+
+// Stand-in for an OTLP collector, recording the bodies of the export requests
+async fn start_collector() -> (String, Arc<Mutex<Vec<Bytes>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let recorded = received.clone();
+    let collector = Router::new().route(
+        "/v1/traces",
+        post(move |body: Bytes| async move {
+            recorded.lock().unwrap().push(body);
+            StatusCode::OK
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, collector).await });
+    (endpoint, received)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_trace_export() {
+    let (endpoint, received) = start_collector().await;
+    let mut config = AppConfig::default();
+    config.reconciler.enabled = false;
+    config.telemetry.enabled = true;
+    config.admin.token = Some(ADMIN_TOKEN.to_string());
+    config.telemetry.endpoint = endpoint;
+    let provider = init_logging(&config.logging, &config.telemetry).unwrap().unwrap();
+    let server = TestServer::start_with_config(config).await;
+
+    // The request continues the trace started by the caller
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let response = reqwest::Client::new()
+        .post(server.url("/v1/account/add-manual"))
+        .bearer_auth(ADMIN_TOKEN)
+        .header("traceparent", format!("00-{}-00f067aa0ba902b7-01", trace_id))
+        .json(&json!({ "id": "carol" }))
+        .send()
+        .await
+        .unwrap();
+    ok_json(response).await;
+
+    // Spans are exported once they end, shortly after the response is sent
+    let trace_id = hex::decode(trace_id).unwrap();
+    for _ in 0..50 {
+        provider.force_flush().unwrap();
+        let exported = received.lock().unwrap().iter().any(|body| {
+            body.windows(trace_id.len()).any(|window| window == trace_id.as_slice())
+        });
+        if exported {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("no span of the caller's trace was exported");
+}